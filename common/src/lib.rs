@@ -0,0 +1,5 @@
+//! bits and pieces shared between days.
+
+pub mod neighbors;
+pub mod netpbm;
+pub mod rng;
@@ -0,0 +1,333 @@
+//! the neighbors of a point on a 2d grid, with a choice of which cells count, what order they come in, and what the edges of the grid are.
+//!
+//! points are `(x, y)` pairs, with `y` increasing downwards, so "north" is `y - 1`.
+
+/// which cells around a point count as its neighbors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Shape {
+    /// the 4 orthogonally adjacent cells.
+    VonNeumann,
+    /// the 8 cells in the 3x3 area around the point.
+    Moore,
+    /// the 3x3 area around the point, *including the point itself*.
+    MooreWithCenter,
+}
+
+/// the order in which neighbors are visited.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Order {
+    /// top to bottom, then left to right.
+    RowMajor,
+    /// counterclockwise, starting east. the center, if included, comes first.
+    CounterClockwise,
+}
+
+const VON_NEUMANN_ROW_MAJOR: [(i8, i8); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+const VON_NEUMANN_CCW: [(i8, i8); 4] = [(1, 0), (0, -1), (-1, 0), (0, 1)];
+const MOORE_WITH_CENTER_ROW_MAJOR: [(i8, i8); 9] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (0, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+const MOORE_WITH_CENTER_CCW: [(i8, i8); 9] = [
+    (0, 0),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+const MOORE_ROW_MAJOR: [(i8, i8); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+impl Shape {
+    /// the offsets from a point to its neighbors, in the given order.
+    pub const fn offsets(self, order: Order) -> &'static [(i8, i8)] {
+        match (self, order) {
+            (Self::VonNeumann, Order::RowMajor) => &VON_NEUMANN_ROW_MAJOR,
+            (Self::VonNeumann, Order::CounterClockwise) => &VON_NEUMANN_CCW,
+            (Self::Moore, Order::RowMajor) => &MOORE_ROW_MAJOR,
+            (Self::Moore, Order::CounterClockwise) => MOORE_WITH_CENTER_CCW.split_at(1).1,
+            (Self::MooreWithCenter, Order::RowMajor) => &MOORE_WITH_CENTER_ROW_MAJOR,
+            (Self::MooreWithCenter, Order::CounterClockwise) => &MOORE_WITH_CENTER_CCW,
+        }
+    }
+}
+
+/// a grid coordinate type. moving off the end of the type's range is treated the same as moving out of bounds.
+pub trait Coord: Copy + PartialOrd {
+    fn offset(self, by: i8) -> Option<Self>;
+}
+
+macro_rules! impl_coord {
+    (unsigned: $($t:ty),*; signed: $($s:ty),*) => {
+        $(impl Coord for $t {
+            fn offset(self, by: i8) -> Option<Self> {
+                if by < 0 {
+                    self.checked_sub(by.unsigned_abs() as $t)
+                } else {
+                    self.checked_add(by as $t)
+                }
+            }
+        })*
+        $(impl Coord for $s {
+            fn offset(self, by: i8) -> Option<Self> {
+                self.checked_add(by as $s)
+            }
+        })*
+    };
+}
+
+impl_coord!(unsigned: u8, u16, u32, u64, usize; signed: i8, i16, i32, i64, isize);
+
+/// a rectangle which neighbors must lie inside. `min` is inclusive and `max` is exclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Bounds<C> {
+    pub min: (C, C),
+    pub max: (C, C),
+}
+
+impl<C: Coord> Bounds<C> {
+    pub const fn new(min: (C, C), max: (C, C)) -> Self {
+        Self { min, max }
+    }
+
+    pub fn contains(&self, (x, y): (C, C)) -> bool {
+        self.min.0 <= x && x < self.max.0 && self.min.1 <= y && y < self.max.1
+    }
+}
+
+/// a kind of neighborhood: its shape, order, and optionally bounds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Neighborhood<C> {
+    pub shape: Shape,
+    pub order: Order,
+    pub bounds: Option<Bounds<C>>,
+}
+
+impl<C: Coord> Neighborhood<C> {
+    /// an unbounded neighborhood.
+    pub const fn new(shape: Shape, order: Order) -> Self {
+        Self {
+            shape,
+            order,
+            bounds: None,
+        }
+    }
+
+    #[must_use]
+    pub const fn bounded(mut self, bounds: Bounds<C>) -> Self {
+        self.bounds = Some(bounds);
+        self
+    }
+
+    /// the neighbors of `center`.
+    pub const fn around(&self, center: (C, C)) -> Neighbors<C> {
+        Neighbors {
+            center,
+            offsets: self.shape.offsets(self.order),
+            bounds: self.bounds,
+            index: 0,
+        }
+    }
+}
+
+/// an iterator over the neighbors of a point, as `(x, y)` pairs.
+#[derive(Clone, Debug)]
+pub struct Neighbors<C> {
+    center: (C, C),
+    offsets: &'static [(i8, i8)],
+    bounds: Option<Bounds<C>>,
+    index: usize,
+}
+
+impl<C: Coord> Iterator for Neighbors<C> {
+    type Item = (C, C);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(&(dx, dy)) = self.offsets.get(self.index) {
+            self.index += 1;
+            let neighbor = self
+                .center
+                .0
+                .offset(dx)
+                .zip(self.center.1.offset(dy))
+                .filter(|&n| self.bounds.is_none_or(|b| b.contains(n)));
+            if neighbor.is_some() {
+                return neighbor;
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.offsets.len() - self.index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rng::Rng;
+
+    use super::*;
+
+    const SHAPES: [Shape; 3] = [Shape::VonNeumann, Shape::Moore, Shape::MooreWithCenter];
+    const ORDERS: [Order; 2] = [Order::RowMajor, Order::CounterClockwise];
+
+    /// the neighbors of `(x, y)` the slow way, in no particular order.
+    fn brute_force(
+        shape: Shape,
+        bounds: Option<Bounds<i64>>,
+        (x, y): (i64, i64),
+    ) -> Vec<(i64, i64)> {
+        let mut res = Vec::new();
+        for ny in y - 1..=y + 1 {
+            for nx in x - 1..=x + 1 {
+                let dist = (nx - x).abs() + (ny - y).abs();
+                let in_shape = match shape {
+                    Shape::VonNeumann => dist == 1,
+                    Shape::Moore => dist > 0,
+                    Shape::MooreWithCenter => true,
+                };
+                if in_shape && bounds.is_none_or(|b| b.contains((nx, ny))) {
+                    res.push((nx, ny));
+                }
+            }
+        }
+        res
+    }
+
+    #[test]
+    fn offsets() {
+        for shape in SHAPES {
+            let mut row_major = shape.offsets(Order::RowMajor).to_vec();
+            let mut ccw = shape.offsets(Order::CounterClockwise).to_vec();
+            assert!(row_major
+                .windows(2)
+                .all(|w| (w[0].1, w[0].0) < (w[1].1, w[1].0)));
+            row_major.sort_unstable();
+            ccw.sort_unstable();
+            assert_eq!(row_major, ccw);
+        }
+
+        // counterclockwise means the angle always increases, with "up" being -y.
+        let angles = Shape::Moore
+            .offsets(Order::CounterClockwise)
+            .iter()
+            .map(|&(dx, dy)| {
+                f64::from(-dy)
+                    .atan2(f64::from(dx))
+                    .rem_euclid(std::f64::consts::TAU)
+            })
+            .collect::<Vec<_>>();
+        assert!(angles.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn corners_and_edges() {
+        let bounds = Bounds::new((0usize, 0), (10, 10));
+        let count = |shape, center| {
+            Neighborhood::new(shape, Order::RowMajor)
+                .bounded(bounds)
+                .around(center)
+                .count()
+        };
+        for (center, expected) in [
+            ((0, 0), [2, 3, 4]),
+            ((9, 0), [2, 3, 4]),
+            ((0, 5), [3, 5, 6]),
+            ((5, 5), [4, 8, 9]),
+            ((9, 9), [2, 3, 4]),
+        ] {
+            for (shape, expected) in SHAPES.into_iter().zip(expected) {
+                assert_eq!(
+                    count(shape, center),
+                    expected,
+                    "{shape:?} around {center:?}"
+                );
+            }
+        }
+
+        // a 1x1 grid only has the point itself.
+        let tiny = Neighborhood::new(Shape::MooreWithCenter, Order::CounterClockwise)
+            .bounded(Bounds::new((3u8, 3), (4, 4)));
+        assert_eq!(tiny.around((3, 3)).collect::<Vec<_>>(), vec![(3, 3)]);
+        // and an empty one has nothing at all.
+        let empty = tiny.bounded(Bounds::new((3, 3), (3, 3)));
+        assert_eq!(empty.around((3, 3)).count(), 0);
+    }
+
+    #[test]
+    fn type_limits() {
+        let moore = Neighborhood::new(Shape::Moore, Order::RowMajor);
+        let moore_i32 = Neighborhood::new(Shape::Moore, Order::RowMajor);
+        assert_eq!(
+            moore.around((0u8, 0)).collect::<Vec<_>>(),
+            vec![(1, 0), (0, 1), (1, 1)]
+        );
+        assert_eq!(moore.around((u8::MAX, u8::MAX)).count(), 3);
+        assert_eq!(moore_i32.around((i32::MIN, 0)).count(), 5);
+        assert_eq!(moore_i32.around((i32::MAX, i32::MIN)).count(), 3);
+        assert_eq!(
+            Neighborhood::new(Shape::Moore, Order::CounterClockwise)
+                .around((5usize, 5))
+                .count(),
+            8
+        );
+    }
+
+    #[test]
+    fn random_against_brute_force() {
+        let mut rng = Rng::new(29);
+        for _ in 0..5000 {
+            let min = (rng.range(-5..5), rng.range(-5..5));
+            let max = (min.0 + rng.range(0..8), min.1 + rng.range(0..8));
+            let bounds = rng.chance(0.8).then(|| Bounds::new(min, max));
+            // mostly inside the bounds, but sometimes on or past the edge.
+            let center = (
+                rng.range(min.0 - 2..max.0 + 2),
+                rng.range(min.1 - 2..max.1 + 2),
+            );
+            let shape = SHAPES[rng.index(3)];
+            let order = ORDERS[rng.index(2)];
+
+            let mut neighborhood = Neighborhood::new(shape, order);
+            if let Some(bounds) = bounds {
+                neighborhood = neighborhood.bounded(bounds);
+            }
+            let got = neighborhood.around(center).collect::<Vec<_>>();
+
+            // same order as the offsets, with the out of bounds ones skipped.
+            let expected_order = shape
+                .offsets(order)
+                .iter()
+                .map(|&(dx, dy)| (center.0 + i64::from(dx), center.1 + i64::from(dy)))
+                .filter(|&n| bounds.is_none_or(|b| b.contains(n)))
+                .collect::<Vec<_>>();
+            assert_eq!(got, expected_order);
+
+            let mut got = got;
+            let mut expected = brute_force(shape, bounds, center);
+            got.sort_unstable();
+            expected.sort_unstable();
+            assert_eq!(got, expected, "{shape:?} around {center:?} in {bounds:?}");
+        }
+    }
+}
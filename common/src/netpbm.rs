@@ -0,0 +1,558 @@
+//! reading and writing [Netpbm](https://netpbm.sourceforge.net/doc/) images (PBM, PGM and PPM), and writing numbered sequences of them.
+
+use std::{
+    fs,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, ensure, Context, Result};
+
+/// the pixels of an image, row by row.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Data {
+    /// PBM. `true` is black.
+    Bitmap(Vec<bool>),
+    /// PGM. `0` is black.
+    Greymap(Vec<u16>),
+    /// PPM.
+    Pixmap(Vec<[u16; 3]>),
+}
+
+/// how the pixel data is stored in the file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    /// human-readable ASCII (`P1`, `P2`, `P3`).
+    Plain,
+    /// binary (`P4`, `P5`, `P6`).
+    Raw,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    /// the largest value a sample can take. unused for bitmaps.
+    pub maxval: u16,
+    /// comment lines in the header, without their leading `#`.
+    pub comments: Vec<String>,
+    pub data: Data,
+}
+
+/// things which can be drawn as a Netpbm image.
+pub trait ToNetpbm {
+    fn to_netpbm(&self) -> Result<Image>;
+}
+
+impl Image {
+    pub fn bitmap(width: usize, height: usize, pixels: Vec<bool>) -> Result<Self> {
+        Self::new(width, height, 1, Data::Bitmap(pixels))
+    }
+
+    pub fn greymap(width: usize, height: usize, maxval: u16, pixels: Vec<u16>) -> Result<Self> {
+        Self::new(width, height, maxval, Data::Greymap(pixels))
+    }
+
+    pub fn pixmap(width: usize, height: usize, maxval: u16, pixels: Vec<[u16; 3]>) -> Result<Self> {
+        Self::new(width, height, maxval, Data::Pixmap(pixels))
+    }
+
+    fn new(width: usize, height: usize, maxval: u16, data: Data) -> Result<Self> {
+        let img = Self {
+            width,
+            height,
+            maxval,
+            comments: Vec::new(),
+            data,
+        };
+        img.check()?;
+        Ok(img)
+    }
+
+    /// make sure there's a pixel for every point in the image, and that no sample is bigger than the maxval.
+    fn check(&self) -> Result<()> {
+        let n = self
+            .width
+            .checked_mul(self.height)
+            .with_context(|| format!("{}x{} is too big", self.width, self.height))?;
+        ensure!(
+            self.len() == n,
+            "expected {n} pixels for {}x{}, found {}",
+            self.width,
+            self.height,
+            self.len()
+        );
+        ensure!(self.maxval > 0, "maxval can't be 0");
+        let max = match &self.data {
+            Data::Bitmap(_) => None,
+            Data::Greymap(px) => px.iter().max().copied(),
+            Data::Pixmap(px) => px.iter().flatten().max().copied(),
+        };
+        if let Some(max) = max.filter(|&max| max > self.maxval) {
+            bail!("sample {max} exceeds maxval {}", self.maxval);
+        }
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        match &self.data {
+            Data::Bitmap(px) => px.len(),
+            Data::Greymap(px) => px.len(),
+            Data::Pixmap(px) => px.len(),
+        }
+    }
+
+    /// the magic number which starts a file of this image with the given encoding.
+    pub const fn magic(&self, encoding: Encoding) -> &'static str {
+        match (&self.data, encoding) {
+            (Data::Bitmap(_), Encoding::Plain) => "P1",
+            (Data::Greymap(_), Encoding::Plain) => "P2",
+            (Data::Pixmap(_), Encoding::Plain) => "P3",
+            (Data::Bitmap(_), Encoding::Raw) => "P4",
+            (Data::Greymap(_), Encoding::Raw) => "P5",
+            (Data::Pixmap(_), Encoding::Raw) => "P6",
+        }
+    }
+
+    /// the conventional file extension for this image.
+    pub const fn extension(&self) -> &'static str {
+        match self.data {
+            Data::Bitmap(_) => "pbm",
+            Data::Greymap(_) => "pgm",
+            Data::Pixmap(_) => "ppm",
+        }
+    }
+
+    /// write the image to `w`, or fail without writing anything if its fields have been changed so that it isn't a valid image any more.
+    pub fn write<W: Write>(&self, mut w: W, encoding: Encoding) -> io::Result<()> {
+        self.check()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        writeln!(w, "{}", self.magic(encoding))?;
+        for comment in &self.comments {
+            writeln!(w, "#{comment}")?;
+        }
+        writeln!(w, "{} {}", self.width, self.height)?;
+        if !matches!(self.data, Data::Bitmap(_)) {
+            writeln!(w, "{}", self.maxval)?;
+        }
+
+        match encoding {
+            Encoding::Plain => self.write_plain(&mut w),
+            Encoding::Raw => self.write_raw(&mut w),
+        }
+    }
+
+    fn write_plain<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        // lines in plain files shouldn't be longer than 70 characters.
+        let mut line = String::new();
+        let mut push = |w: &mut W, sample: &str, end_of_row: bool| {
+            if !line.is_empty() && line.len() + 1 + sample.len() > 70 {
+                writeln!(w, "{line}")?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(sample);
+            if end_of_row {
+                writeln!(w, "{line}")?;
+                line.clear();
+            }
+            io::Result::Ok(())
+        };
+
+        for i in 0..self.len() {
+            let end_of_row = (i + 1) % self.width.max(1) == 0;
+            match &self.data {
+                Data::Bitmap(px) => push(w, if px[i] { "1" } else { "0" }, end_of_row)?,
+                Data::Greymap(px) => push(w, &px[i].to_string(), end_of_row)?,
+                Data::Pixmap(px) => {
+                    let [r, g, b] = px[i];
+                    push(w, &format!("{r} {g} {b}"), end_of_row)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_raw<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let wide = self.maxval > u8::MAX as u16;
+        let mut buf = Vec::new();
+        let mut sample = |s: u16| {
+            if wide {
+                buf.extend_from_slice(&s.to_be_bytes());
+            } else {
+                buf.push(u8::try_from(s).expect("samples are checked against the maxval"));
+            }
+        };
+
+        match &self.data {
+            Data::Bitmap(px) => {
+                // each row starts on a new byte, most significant bit first.
+                for row in px.chunks(self.width.max(1)) {
+                    for byte in row.chunks(8) {
+                        let bits = byte
+                            .iter()
+                            .enumerate()
+                            .fold(0u8, |acc, (i, &b)| acc | (u8::from(b) << (7 - i)));
+                        buf.push(bits);
+                    }
+                }
+            }
+            Data::Greymap(px) => px.iter().for_each(|&s| sample(s)),
+            Data::Pixmap(px) => px.iter().flatten().for_each(|&s| sample(s)),
+        }
+        w.write_all(&buf)
+    }
+
+    /// the bytes of a file holding this image.
+    pub fn to_bytes(&self, encoding: Encoding) -> io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        self.write(&mut out, encoding)?;
+        Ok(out)
+    }
+
+    pub fn read<R: BufRead>(mut r: R) -> Result<Self> {
+        let mut bytes = Vec::new();
+        r.read_to_end(&mut bytes)?;
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut header = Header {
+            bytes,
+            pos: 0,
+            comments: Vec::new(),
+        };
+        let magic = header.token().context("missing magic number")?;
+        let (encoding, kind) = match magic {
+            b"P1" => (Encoding::Plain, 1),
+            b"P2" => (Encoding::Plain, 2),
+            b"P3" => (Encoding::Plain, 3),
+            b"P4" => (Encoding::Raw, 1),
+            b"P5" => (Encoding::Raw, 2),
+            b"P6" => (Encoding::Raw, 3),
+            _ => bail!("unknown magic number `{}`", String::from_utf8_lossy(magic)),
+        };
+        let width = header.number("width")?;
+        let height = header.number("height")?;
+        let maxval = if kind == 1 {
+            1
+        } else {
+            let maxval = header.number("maxval")?;
+            ensure!(
+                (1..=u16::MAX as usize).contains(&maxval),
+                "maxval {maxval} out of range"
+            );
+            maxval as u16
+        };
+        let channels = if kind == 3 { 3 } else { 1 };
+        let n = width
+            .checked_mul(height)
+            .filter(|n| n.checked_mul(channels * 2).is_some())
+            .with_context(|| format!("{width}x{height} is too big"))?;
+
+        let samples = match encoding {
+            Encoding::Plain => {
+                // every sample takes at least a byte, so there can't be more than there are bytes left.
+                let remaining = header.bytes.len().saturating_sub(header.pos);
+                let mut samples = Vec::with_capacity((n * channels).min(remaining));
+                while samples.len() < n * channels {
+                    let sample = if kind == 1 {
+                        // plain bitmaps don't need whitespace between pixels.
+                        header.skip_space();
+                        let sample = match header.bytes.get(header.pos) {
+                            Some(b'0') => 0,
+                            Some(b'1') => 1,
+                            Some(c) => bail!("invalid bitmap pixel `{}`", char::from(*c)),
+                            None => bail!("unexpected end of data"),
+                        };
+                        header.pos += 1;
+                        sample
+                    } else {
+                        header.number("sample")?
+                    };
+                    ensure!(
+                        sample <= maxval as usize,
+                        "sample {sample} exceeds maxval {maxval}"
+                    );
+                    samples.push(sample as u16);
+                }
+                samples
+            }
+            Encoding::Raw => {
+                // exactly one whitespace character separates the header from the data.
+                ensure!(
+                    header
+                        .bytes
+                        .get(header.pos)
+                        .is_some_and(u8::is_ascii_whitespace),
+                    "expected whitespace before the data"
+                );
+                header.pos += 1;
+                let data = header.bytes.get(header.pos..).unwrap_or_default();
+                if kind == 1 {
+                    let row_bytes = width.div_ceil(8);
+                    let len = row_bytes
+                        .checked_mul(height)
+                        .with_context(|| format!("{width}x{height} is too big"))?;
+                    ensure!(data.len() >= len, "unexpected end of data");
+                    (0..height)
+                        .flat_map(|y| (0..width).map(move |x| (y, x)))
+                        .map(|(y, x)| u16::from(data[y * row_bytes + x / 8] >> (7 - x % 8) & 1))
+                        .collect()
+                } else if maxval > u8::MAX as u16 {
+                    ensure!(data.len() >= n * channels * 2, "unexpected end of data");
+                    data.chunks(2)
+                        .take(n * channels)
+                        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+                        .collect()
+                } else {
+                    ensure!(data.len() >= n * channels, "unexpected end of data");
+                    data[..n * channels].iter().map(|&b| u16::from(b)).collect()
+                }
+            }
+        };
+
+        let data = match kind {
+            1 => Data::Bitmap(samples.into_iter().map(|s| s == 1).collect()),
+            2 => Data::Greymap(samples),
+            _ => Data::Pixmap(samples.chunks(3).map(|c| [c[0], c[1], c[2]]).collect()),
+        };
+
+        Ok(Self {
+            width,
+            height,
+            maxval,
+            comments: header.comments,
+            data,
+        })
+    }
+}
+
+/// a cursor over the whitespace-separated, comment-laden header of a Netpbm file.
+struct Header<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    comments: Vec<String>,
+}
+
+impl<'a> Header<'a> {
+    fn skip_space(&mut self) {
+        while let Some(&c) = self.bytes.get(self.pos) {
+            if c == b'#' {
+                let end = self.bytes[self.pos..]
+                    .iter()
+                    .position(|&c| c == b'\n')
+                    .map_or(self.bytes.len(), |i| self.pos + i);
+                let comment = &self.bytes[self.pos + 1..end];
+                let comment = comment.strip_suffix(b"\r").unwrap_or(comment);
+                self.comments
+                    .push(String::from_utf8_lossy(comment).into_owned());
+                self.pos = end;
+            } else if c.is_ascii_whitespace() {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn token(&mut self) -> Option<&'a [u8]> {
+        self.skip_space();
+        let start = self.pos;
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|c| !c.is_ascii_whitespace() && *c != b'#')
+        {
+            self.pos += 1;
+        }
+        (self.pos > start).then(|| &self.bytes[start..self.pos])
+    }
+
+    fn number(&mut self, what: &str) -> Result<usize> {
+        let token = self.token().context(format!("missing {what}"))?;
+        std::str::from_utf8(token)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .context(format!(
+                "invalid {what} `{}`",
+                String::from_utf8_lossy(token)
+            ))
+    }
+}
+
+/// writes images to numbered files in a directory, for turning a simulation into an animation.
+pub struct SequenceWriter {
+    dir: PathBuf,
+    prefix: String,
+    encoding: Encoding,
+    next: usize,
+}
+
+impl SequenceWriter {
+    /// create `dir` if it doesn't exist and prepare to write `{prefix}_0000.*`, `{prefix}_0001.*`, ... into it.
+    pub fn new(dir: impl AsRef<Path>, prefix: &str, encoding: Encoding) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir).context(format!("couldn't create {}", dir.display()))?;
+        Ok(Self {
+            dir,
+            prefix: prefix.to_owned(),
+            encoding,
+            next: 0,
+        })
+    }
+
+    /// the number of images written so far.
+    pub const fn count(&self) -> usize {
+        self.next
+    }
+
+    /// write the next image in the sequence, returning its path.
+    pub fn write(&mut self, img: &Image) -> Result<PathBuf> {
+        let path = self.dir.join(format!(
+            "{}_{:04}.{}",
+            self.prefix,
+            self.next,
+            img.extension()
+        ));
+        let file =
+            fs::File::create(&path).context(format!("couldn't create {}", path.display()))?;
+        let mut w = io::BufWriter::new(file);
+        img.write(&mut w, self.encoding)?;
+        w.flush()?;
+        self.next += 1;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples() -> Vec<Image> {
+        let mut with_comment =
+            Image::bitmap(3, 2, vec![true, false, true, false, true, false]).unwrap();
+        with_comment.comments.push(" made by a test".to_owned());
+        vec![
+            with_comment,
+            Image::bitmap(10, 1, (0..10).map(|i| i % 3 == 0).collect()).unwrap(),
+            Image::greymap(2, 2, 255, vec![0, 64, 128, 255]).unwrap(),
+            Image::greymap(40, 3, 1000, (0..120).map(|i| i * 8).collect()).unwrap(),
+            Image::pixmap(2, 1, 255, vec![[255, 0, 0], [0, 0, 255]]).unwrap(),
+            Image::pixmap(1, 2, 65535, vec![[1, 2, 3], [65535, 256, 0]]).unwrap(),
+        ]
+    }
+
+    #[test]
+    fn round_trip() {
+        for img in samples() {
+            for encoding in [Encoding::Plain, Encoding::Raw] {
+                let bytes = img.to_bytes(encoding).unwrap();
+                let read = Image::from_bytes(&bytes).unwrap();
+                assert_eq!(read, img, "{}", img.magic(encoding));
+                assert_eq!(read.to_bytes(encoding).unwrap(), bytes);
+            }
+        }
+    }
+
+    #[test]
+    fn plain_format() {
+        let img = Image::greymap(2, 2, 255, vec![0, 64, 128, 255]).unwrap();
+        assert_eq!(
+            img.to_bytes(Encoding::Plain).unwrap(),
+            b"P2\n2 2\n255\n0 64\n128 255\n"
+        );
+
+        let img = Image::bitmap(9, 1, vec![true; 9]).unwrap();
+        assert_eq!(img.to_bytes(Encoding::Raw).unwrap(), b"P4\n9 1\n\xff\x80");
+    }
+
+    #[test]
+    fn invalid() {
+        let err = |img: Result<Image>| img.unwrap_err().to_string();
+        assert_eq!(
+            err(Image::bitmap(3, 2, vec![true; 5])),
+            "expected 6 pixels for 3x2, found 5"
+        );
+        assert_eq!(
+            err(Image::greymap(usize::MAX, 2, 255, Vec::new())),
+            format!("{}x2 is too big", usize::MAX)
+        );
+        assert_eq!(
+            err(Image::greymap(2, 1, 255, vec![0, 256])),
+            "sample 256 exceeds maxval 255"
+        );
+        assert!(Image::pixmap(1, 1, 0, vec![[0; 3]]).is_err());
+
+        // changing the fields afterwards doesn't get past `write` either.
+        let mut img = Image::greymap(2, 1, 255, vec![0, 255]).unwrap();
+        img.maxval = 100;
+        assert!(img.to_bytes(Encoding::Raw).is_err());
+        img.maxval = 255;
+        img.width = 3;
+        assert!(img.to_bytes(Encoding::Plain).is_err());
+    }
+
+    #[test]
+    fn read_loose() {
+        let img = Image::from_bytes(b"P1 # a comment\n3 # another\n 2\n101\n0 1 0").unwrap();
+        assert_eq!(img, {
+            let mut img = Image::bitmap(3, 2, vec![true, false, true, false, true, false]).unwrap();
+            img.comments = vec![" a comment".to_owned(), " another".to_owned()];
+            img
+        });
+
+        assert!(Image::from_bytes(b"P2\n2 1\n3\n1 4\n").is_err());
+        assert!(Image::from_bytes(b"P5\n2 2\n255\n\x00").is_err());
+        assert!(Image::from_bytes(b"P7\n1 1\n").is_err());
+    }
+
+    #[test]
+    fn read_hostile() {
+        // sizes which don't fit in memory, or in a usize at all, are errors rather than aborts.
+        for bytes in [
+            &b"P1\n4000000000 4000000000\n0\n"[..],
+            b"P4\n4000000000 4000000000\n\x00",
+            b"P3\n4000000000 4000000000\n255\n0 0 0\n",
+            b"P6\n3000000000 3000000000\n65535\n\x00",
+            b"P2\n100000 100000\n255\n1 2 3\n",
+        ] {
+            assert!(Image::from_bytes(bytes).is_err());
+        }
+        // raw data has to be separated from the header by whitespace.
+        assert!(Image::from_bytes(b"P5\n1 1\n255").is_err());
+        assert!(Image::from_bytes(b"P5\n1 1\n255#\x07").is_err());
+        assert_eq!(
+            Image::from_bytes(b"P5\n1 1\n255\t\x07").unwrap().data,
+            Data::Greymap(vec![7])
+        );
+    }
+
+    #[test]
+    fn sequence() {
+        let dir = std::env::temp_dir().join(format!("netpbm-sequence-{}", std::process::id()));
+        let mut writer = SequenceWriter::new(&dir, "frame", Encoding::Raw).unwrap();
+        let paths = samples()
+            .iter()
+            .map(|img| writer.write(img).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(writer.count(), 6);
+        assert_eq!(paths[2], dir.join("frame_0002.pgm"));
+
+        for (img, path) in samples().iter().zip(paths) {
+            let bytes = fs::read(path).unwrap();
+            assert_eq!(bytes, img.to_bytes(Encoding::Raw).unwrap());
+            assert_eq!(
+                Image::from_bytes(&bytes)
+                    .unwrap()
+                    .to_bytes(Encoding::Raw)
+                    .unwrap(),
+                bytes
+            );
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
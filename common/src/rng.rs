@@ -0,0 +1,102 @@
+//! a tiny seeded random number generator, for tests and generated inputs which need to be reproducible.
+
+use std::ops::Range;
+
+/// [SplitMix64](https://prng.di.unimi.it/splitmix64.c). not remotely cryptographic, but fast, and the same seed always gives the same numbers.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub const fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// a number in `0..n`. `n` must not be zero.
+    pub fn below(&mut self, n: u64) -> u64 {
+        assert!(n > 0, "empty range");
+        // multiply-shift is close enough to uniform for our purposes.
+        ((u128::from(self.next_u64()) * u128::from(n)) >> 64) as u64
+    }
+
+    /// a number in `range`, which must not be empty.
+    pub fn range(&mut self, range: Range<i64>) -> i64 {
+        assert!(range.start < range.end, "empty range");
+        let span = range.end.abs_diff(range.start);
+        range.start.wrapping_add(self.below(span) as i64)
+    }
+
+    /// an index into a slice of length `len`.
+    pub fn index(&mut self, len: usize) -> usize {
+        self.below(len as u64) as usize
+    }
+
+    /// a float in `0.0..1.0`.
+    pub fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// `true` with probability `p`.
+    pub fn chance(&mut self, p: f64) -> bool {
+        self.unit() < p
+    }
+
+    /// a normally distributed float with mean 0 and standard deviation 1.
+    pub fn normal(&mut self) -> f64 {
+        // box-muller. `1.0 - unit()` is never zero, so the log is finite.
+        let u = 1.0 - self.unit();
+        let v = self.unit();
+        (-2.0 * u.ln()).sqrt() * (std::f64::consts::TAU * v).cos()
+    }
+
+    pub fn shuffle<T>(&mut self, slice: &mut [T]) {
+        for i in (1..slice.len()).rev() {
+            slice.swap(i, self.index(i + 1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reproducible() {
+        let a = (0..10).map(|_| Rng::new(7).next_u64()).collect::<Vec<_>>();
+        assert!(a.windows(2).all(|w| w[0] == w[1]));
+
+        let mut rng = Rng::new(7);
+        let mut other = Rng::new(8);
+        assert_ne!(rng.next_u64(), other.next_u64());
+    }
+
+    #[test]
+    fn ranges() {
+        let mut rng = Rng::new(1);
+        let mut seen = [false; 7];
+        for _ in 0..1000 {
+            let n = rng.range(-3..4);
+            assert!((-3..4).contains(&n));
+            seen[(n + 3) as usize] = true;
+
+            let f = rng.unit();
+            assert!((0.0..1.0).contains(&f));
+        }
+        assert!(seen.iter().all(|&s| s));
+        assert_eq!(rng.range(i64::MIN..i64::MIN + 1), i64::MIN);
+
+        let mut v = (0..20).collect::<Vec<_>>();
+        rng.shuffle(&mut v);
+        v.sort_unstable();
+        assert_eq!(v, (0..20).collect::<Vec<_>>());
+    }
+}
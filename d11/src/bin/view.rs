@@ -0,0 +1,145 @@
+use std::{
+    env, fs,
+    io::{self, BufRead, Read, Write},
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+
+use common::netpbm::{Encoding, SequenceWriter};
+
+use d11::view::{self, Control, Viewer};
+
+const USAGE: &str =
+    "usage: view <input> [--capture <dir> | --export <dir>] [--steps <n>] [--delay <ms>] [--plain] [--grey]";
+
+/// set whether the terminal on stdin echoes and waits for newlines, so that single keypresses reach us.
+fn set_raw(raw: bool) {
+    let args: &[&str] = if raw {
+        &["-icanon", "-echo"]
+    } else {
+        &["icanon", "echo"]
+    };
+    // not being able to do this just means keys need an enter after them.
+    let _ = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .status();
+}
+
+fn animate(mut viewer: Viewer, steps: Option<usize>) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for byte in io::stdin().lock().bytes() {
+            let Ok(byte) = byte else { break };
+            if let Some(control) = Control::from_key(byte) {
+                if tx.send(control).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    let mut stdout = io::stdout().lock();
+    let mut next_step = Instant::now() + viewer.delay;
+    loop {
+        write!(stdout, "{}{}", view::CLEAR_SCREEN, viewer.frame())?;
+        writeln!(stdout, "[space] pause  [n] step  [+/-] speed  [q] quit")?;
+        stdout.flush()?;
+
+        if steps.is_some_and(|steps| viewer.steps >= steps) {
+            break;
+        }
+
+        let control = if viewer.paused {
+            rx.recv().ok()
+        } else {
+            match rx.recv_timeout(next_step.saturating_duration_since(Instant::now())) {
+                Ok(control) => Some(control),
+                Err(RecvTimeoutError::Timeout) => {
+                    viewer.advance();
+                    next_step = Instant::now() + viewer.delay;
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => None,
+            }
+        };
+
+        match control {
+            Some(control) => {
+                if !viewer.handle(control) {
+                    break;
+                }
+                next_step = Instant::now() + viewer.delay;
+            }
+            // stdin closed, so nothing can unpause us.
+            None if viewer.paused => break,
+            None => {
+                thread::sleep(next_step.saturating_duration_since(Instant::now()));
+                viewer.advance();
+                next_step = Instant::now() + viewer.delay;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    simple_logger::init_with_env().context("couldn't initialize logger")?;
+
+    let mut input = None;
+    let mut capture_dir = None;
+    let mut export_dir = None;
+    let mut steps = None;
+    let mut delay = None;
+    let mut colour = true;
+    let mut grey = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--capture" => capture_dir = Some(PathBuf::from(args.next().context(USAGE)?)),
+            "--export" => export_dir = Some(PathBuf::from(args.next().context(USAGE)?)),
+            "--steps" => steps = Some(args.next().context(USAGE)?.parse::<usize>()?),
+            "--delay" => delay = Some(args.next().context(USAGE)?.parse::<u64>()?),
+            "--plain" => colour = false,
+            "--grey" => grey = true,
+            _ if input.is_none() => input = Some(PathBuf::from(arg)),
+            _ => bail!(USAGE),
+        }
+    }
+
+    let input = input.context(USAGE)?;
+    let file = fs::File::open(&input).context(format!("couldn't open {}", input.display()))?;
+    let grid = d11::parse_input(&mut io::BufReader::new(file).lines())?;
+
+    if let Some(dir) = export_dir {
+        let mut writer = SequenceWriter::new(&dir, "step", Encoding::Raw)?;
+        let mut grid = grid;
+        let (paths, _) =
+            d11::export::export_run(&mut grid, steps.unwrap_or(100), &mut writer, !grey)?;
+        println!("wrote {} images to {}", paths.len(), dir.display());
+        return Ok(());
+    }
+
+    let mut viewer = Viewer::new(grid);
+    viewer.colour = colour;
+    if let Some(delay) = delay {
+        viewer.delay = Duration::from_millis(delay).clamp(view::MIN_DELAY, view::MAX_DELAY);
+    }
+
+    if let Some(dir) = capture_dir {
+        let paths = view::capture(&mut viewer, steps.unwrap_or(100), &dir)?;
+        println!("wrote {} frames to {}", paths.len(), dir.display());
+        return Ok(());
+    }
+
+    set_raw(true);
+    let res = animate(viewer, steps);
+    set_raw(false);
+    res
+}
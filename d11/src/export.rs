@@ -0,0 +1,130 @@
+//! drawing a `Grid` as Netpbm images, and dumping whole runs as image sequences.
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use common::netpbm::{Image, SequenceWriter, ToNetpbm};
+
+use crate::Grid;
+
+pub const MAXVAL: u16 = 255;
+
+/// the colour of an octopus which flashed during the last step.
+pub const FLASH_COLOUR: [u16; 3] = [255, 220, 0];
+
+/// the grey level of a cell. energy 1 through 9 fades up from black in tenths of `MAXVAL`, and cells which flashed during the last step (energy 0) are white. anything above 9 is drawn like 9.
+pub const fn grey(energy: u8) -> u16 {
+    match energy {
+        0 => MAXVAL,
+        n if n > 9 => MAXVAL / 10 * 9,
+        n => MAXVAL / 10 * n as u16,
+    }
+}
+
+impl Grid {
+    /// draw `self` as a greymap. see `grey`.
+    pub fn to_pgm(&self) -> Result<Image> {
+        let (rows, cols) = self.dimensions();
+        let pixels = self.cells().iter().map(|&n| grey(n)).collect();
+        Image::greymap(cols, rows, MAXVAL, pixels)
+    }
+
+    /// draw `self` as a pixmap, with energy in grey and flashes in `FLASH_COLOUR`.
+    pub fn to_ppm(&self) -> Result<Image> {
+        let (rows, cols) = self.dimensions();
+        let pixels = self
+            .cells()
+            .iter()
+            .map(|&n| match n {
+                0 => FLASH_COLOUR,
+                n => [grey(n); 3],
+            })
+            .collect();
+        Image::pixmap(cols, rows, MAXVAL, pixels)
+    }
+}
+
+impl ToNetpbm for Grid {
+    fn to_netpbm(&self) -> Result<Image> {
+        self.to_ppm()
+    }
+}
+
+/// step `grid` `steps` times, writing the initial state and the state after each step to `writer` as PPMs, or PGMs if `colour` is unset. returns the paths written and the total number of flashes.
+pub fn export_run(
+    grid: &mut Grid,
+    steps: usize,
+    writer: &mut SequenceWriter,
+    colour: bool,
+) -> Result<(Vec<PathBuf>, usize)> {
+    let draw = |grid: &Grid| if colour { grid.to_ppm() } else { grid.to_pgm() };
+    let mut flashes = 0;
+    let mut paths = vec![writer.write(&draw(grid)?)?];
+    for _ in 0..steps {
+        grid.step(&mut flashes);
+        paths.push(writer.write(&draw(grid)?)?);
+    }
+    Ok((paths, flashes))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use common::netpbm::{Data, Encoding};
+
+    use super::*;
+
+    fn example() -> Grid {
+        let input = include_str!("../../input/11e.txt");
+        crate::parse_input(&mut std::io::BufRead::lines(input.as_bytes())).unwrap()
+    }
+
+    #[test]
+    fn pixels() {
+        let mut grid = example();
+        grid.step(&mut 0);
+        grid.step(&mut 0);
+        let pgm = grid.to_pgm().unwrap();
+        let Data::Greymap(grey) = &pgm.data else {
+            panic!("expected a greymap");
+        };
+        assert_eq!(&grey[..3], &[200, 200, MAXVAL]);
+        assert!((0..=u8::MAX).all(|n| super::grey(n) <= MAXVAL));
+
+        let ppm = grid.to_netpbm().unwrap();
+        let Data::Pixmap(rgb) = &ppm.data else {
+            panic!("expected a pixmap");
+        };
+        assert_eq!(&rgb[..3], &[[200; 3], [200; 3], FLASH_COLOUR]);
+    }
+
+    #[test]
+    fn round_trip() {
+        for (colour, encoding) in [(true, Encoding::Raw), (false, Encoding::Plain)] {
+            let dir =
+                std::env::temp_dir().join(format!("d11-export-{colour}-{}", std::process::id()));
+            let mut writer = SequenceWriter::new(&dir, "step", encoding).unwrap();
+            let (paths, flashes) = export_run(&mut example(), 10, &mut writer, colour).unwrap();
+            assert_eq!(paths.len(), 11);
+            assert_eq!(flashes, 204);
+
+            let mut grid = example();
+            for (i, path) in paths.iter().enumerate() {
+                if i > 0 {
+                    grid.step(&mut 0);
+                }
+                let expected = if colour { grid.to_ppm() } else { grid.to_pgm() }.unwrap();
+                let bytes = fs::read(path).unwrap();
+                assert_eq!(bytes, expected.to_bytes(encoding).unwrap());
+
+                let read = Image::from_bytes(&bytes).unwrap();
+                assert_eq!(read, expected);
+                assert_eq!(read.to_bytes(encoding).unwrap(), bytes);
+            }
+
+            fs::remove_dir_all(dir).unwrap();
+        }
+    }
+}
@@ -1,14 +1,22 @@
-use anyhow::{Context, Result};
+pub mod export;
+pub mod parallel;
+pub mod provenance;
+pub mod view;
+
+use anyhow::{bail, Context, Result};
+
+use common::neighbors::{self, Bounds, Neighborhood, Order, Shape};
 
 use log::trace;
+
 use std::{
-    collections::HashSet,
     fmt::Display,
     io::{BufRead, Lines},
-    ops::{Deref, DerefMut},
+    ops::{Index, IndexMut},
     fmt::Write,
 };
 
+/// the size of the grid in the puzzle input.
 pub const ROWS: usize = 10;
 pub const COLS: usize = 10;
 
@@ -23,6 +31,15 @@ impl Point {
         Self { row, col }
     }
 
+    pub const fn row(&self) -> usize {
+        self.row
+    }
+
+    pub const fn col(&self) -> usize {
+        self.col
+    }
+
+    /// the points around this one in a `ROWS` by `COLS` grid. see `Grid::neighbors` for grids of other sizes.
     pub fn neighbors(&self) -> Neighbors {
         Neighbors::new(*self)
     }
@@ -34,14 +51,22 @@ impl Display for Point {
     }
 }
 
-pub struct Neighbors {
-    center: Point,
-    index: usize,
-}
+/// the points around a point inside a grid, counterclockwise from the east.
+#[derive(Clone, Debug)]
+pub struct Neighbors(neighbors::Neighbors<usize>);
 
 impl Neighbors {
+    /// the neighbors of `center` in a `ROWS` by `COLS` grid.
     pub fn new(center: Point) -> Self {
-        Self { center, index: 0 }
+        Self::within(center, ROWS, COLS)
+    }
+
+    fn within(center: Point, rows: usize, cols: usize) -> Self {
+        Self(
+            Neighborhood::new(Shape::Moore, Order::CounterClockwise)
+                .bounded(Bounds::new((0, 0), (cols, rows)))
+                .around((center.col, center.row)),
+        )
     }
 }
 
@@ -49,40 +74,22 @@ impl Iterator for Neighbors {
     type Item = Point;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let Self {
-            center: Point { row, col },
-            ..
-        } = *self;
-
-        let nth_neighbor = |n| match n {
-            0 => (col < COLS - 1).then(|| Point::new(row, col + 1)),
-            1 => (row > 0 && col < COLS - 1).then(|| Point::new(row - 1, col + 1)),
-            2 => (row > 0).then(|| Point::new(row - 1, col)),
-            3 => (row > 0 && col > 0).then(|| Point::new(row - 1, col - 1)),
-            4 => (col > 0).then(|| Point::new(row, col - 1)),
-            5 => (row < ROWS - 1 && col > 0).then(|| Point::new(row + 1, col - 1)),
-            6 => (row < ROWS - 1).then(|| Point::new(row + 1, col)),
-            7 => (row < ROWS - 1 && col < COLS - 1).then(|| Point::new(row + 1, col + 1)),
-            _ => None,
-        };
-
-        while self.index < 8 && nth_neighbor(self.index).is_none() {
-            self.index += 1;
-        }
-
-        let neighbor = nth_neighbor(self.index);
-        self.index += 1;
-        neighbor
+        self.0.next().map(|(col, row)| Point::new(row, col))
     }
 }
 
-#[derive(Default, Debug)]
-pub struct Grid([[u8; COLS]; ROWS]);
+/// a grid of octopus energy levels, stored row by row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid {
+    rows: usize,
+    cols: usize,
+    cells: Vec<u8>,
+}
 
 impl Display for Grid {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut out = String::new();
-        self.iter().for_each(|row| {
+        self.rows().for_each(|row| {
             row.iter().for_each(|num| {
                 write!(out, "{}", num).unwrap();
             });
@@ -92,54 +99,96 @@ impl Display for Grid {
     }
 }
 
-impl Deref for Grid {
-    type Target = [[u8; COLS]; ROWS];
+impl Index<usize> for Grid {
+    type Output = [u8];
+
+    fn index(&self, row: usize) -> &Self::Output {
+        &self.cells[row * self.cols..(row + 1) * self.cols]
+    }
+}
 
-    fn deref(&self) -> &Self::Target {
-        &self.0
+impl IndexMut<usize> for Grid {
+    fn index_mut(&mut self, row: usize) -> &mut Self::Output {
+        &mut self.cells[row * self.cols..(row + 1) * self.cols]
     }
 }
 
-impl DerefMut for Grid {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+/// a `ROWS` by `COLS` grid, like the puzzle's.
+impl Default for Grid {
+    fn default() -> Self {
+        Self::new(ROWS, COLS)
     }
 }
 
 impl Grid {
+    /// a grid of octopi with no energy.
+    pub fn new(rows: usize, cols: usize) -> Self {
+        Self {
+            rows,
+            cols,
+            cells: vec![0; rows * cols],
+        }
+    }
+
+    /// (rows, cols)
+    pub const fn dimensions(&self) -> (usize, usize) {
+        (self.rows, self.cols)
+    }
+
+    /// every cell, row by row.
+    pub fn cells(&self) -> &[u8] {
+        &self.cells
+    }
+
+    pub fn cells_mut(&mut self) -> &mut [u8] {
+        &mut self.cells
+    }
+
+    /// every row in order.
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        // `max(1)` so that a grid with no columns still has (empty) rows.
+        self.cells.chunks(self.cols.max(1)).take(self.rows)
+    }
+
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [u8]> {
+        self.cells.chunks_mut(self.cols.max(1)).take(self.rows)
+    }
+
+    /// the points around `point` which are inside the grid, counterclockwise from the east.
+    pub fn neighbors(&self, point: Point) -> Neighbors {
+        Neighbors::within(point, self.rows, self.cols)
+    }
+
     pub fn step(&mut self, flashes: &mut usize) {
-        for row in 0..ROWS {
-            for col in 0..COLS {
-                self[row][col] += 1;
-            }
+        for cell in &mut self.cells {
+            *cell += 1;
         }
 
+        // a cell is queued at most once, and once it's queued there's no point bumping it up any more since it's about to be reset.
         trace!("looking for initial flashers");
         let mut queue = Vec::new();
-        for row in 0..ROWS {
-            for col in 0..COLS {
-                let val = self[row][col];
-                if val > 9 {
-                    let point = Point::new(row, col);
-                    trace!("queueing {}", point);
-                    queue.push(point);
-                }
+        let mut queued = vec![false; self.cells.len()];
+        for (i, &cell) in self.cells.iter().enumerate() {
+            if cell > 9 {
+                trace!("queueing {}", Point::new(i / self.cols, i % self.cols));
+                queue.push(i);
+                queued[i] = true;
             }
         }
 
-        let mut already_flashed = HashSet::new();
-        while let Some(point) = queue.pop() {
+        while let Some(i) = queue.pop() {
+            let point = Point::new(i / self.cols, i % self.cols);
             trace!("popping {}", point);
             *flashes += 1;
-            self[point.row][point.col] = 0;
-            already_flashed.insert(point);
-
-            let neighbors = point.neighbors();
-            for neighbor in neighbors {
-                if !already_flashed.contains(&neighbor) {
-                    self[neighbor.row][neighbor.col] += 1;
-                    if self[neighbor.row][neighbor.col] > 9 && !queue.contains(&neighbor) {
-                        queue.push(neighbor);
+            self.cells[i] = 0;
+
+            for neighbor in Neighbors::within(point, self.rows, self.cols) {
+                let j = neighbor.row * self.cols + neighbor.col;
+                if !queued[j] {
+                    self.cells[j] += 1;
+                    if self.cells[j] > 9 {
+                        queue.push(j);
+                        queued[j] = true;
                     }
                 }
             }
@@ -147,26 +196,68 @@ impl Grid {
     }
 }
 
+/// the first `COLS` digits of `vec`, padded with 0s.
 pub fn vec_to_row(vec: Vec<u8>) -> [u8; COLS] {
     let mut res = [0; COLS];
-    for col in 0..vec.len() {
-        res[col] = vec[col];
+    for (cell, n) in res.iter_mut().zip(vec) {
+        *cell = n;
     }
     res
 }
 
+/// read a grid of digits, stopping at the end of the input or at a blank line. every row must be the same length.
 pub fn parse_input<R: BufRead>(lines: &mut Lines<R>) -> Result<Grid> {
-    let mut grid = Grid::default();
-    for row in 0..ROWS {
-        let line = lines
-            .next()
-            .context(format!("row {}: unexpected end of input", row + 1))??;
+    let mut cells = Vec::new();
+    let mut rows = 0;
+    let mut cols = None;
+    for line in lines {
+        let line = line?;
+        if line.is_empty() {
+            break;
+        }
         let digits = line
             .chars()
             .map(|c| c.to_digit(10).map(|n| n as u8))
             .collect::<Option<Vec<u8>>>()
-            .context("non-digit char in input")?;
-        grid[row] = vec_to_row(digits);
+            .context(format!("row {}: non-digit char in input", rows + 1))?;
+        match cols {
+            None => cols = Some(digits.len()),
+            Some(cols) if cols != digits.len() => bail!(
+                "row {}: expected {cols} digits, found {}",
+                rows + 1,
+                digits.len()
+            ),
+            _ => (),
+        }
+        cells.extend(digits);
+        rows += 1;
+    }
+
+    let cols = cols.context("row 1: unexpected end of input")?;
+    Ok(Grid { rows, cols, cells })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn neighbors() {
+        let corner = Point::new(0, COLS - 1);
+        assert_eq!(
+            corner.neighbors().collect::<Vec<_>>(),
+            [Point::new(0, 8), Point::new(1, 8), Point::new(1, 9)]
+        );
+        assert_eq!(Point::new(4, 4).neighbors().count(), 8);
+
+        let grid = Grid::default();
+        assert_eq!(grid.dimensions(), (ROWS, COLS));
+        assert!(grid
+            .neighbors(corner)
+            .eq(Neighbors::new(corner)));
+        // a bigger grid goes past the puzzle's edges.
+        assert_eq!(Grid::new(20, 20).neighbors(corner).count(), 5);
+
+        assert_eq!(vec_to_row(vec![1, 2, 3]), [1, 2, 3, 0, 0, 0, 0, 0, 0, 0]);
     }
-    Ok(grid)
 }
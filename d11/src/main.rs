@@ -2,7 +2,7 @@ use anyhow::{Result, Context};
 
 use log::trace;
 
-use std::io;
+use std::{io, thread};
 
 fn main() -> Result<()> {
     simple_logger::init_with_env().context("couldn't initialize logger")?;
@@ -11,9 +11,18 @@ fn main() -> Result<()> {
     let mut grid = d11::parse_input(&mut lines)?;
     trace!("initial grid: {}", grid);
 
+    // threads only pay for themselves on big grids.
+    let (rows, cols) = grid.dimensions();
+    let threads = (rows * cols >= d11::parallel::MIN_PARALLEL_CELLS)
+        .then(|| thread::available_parallelism().ok())
+        .flatten();
+
     let mut flashes = 0;
     for _ in 0..100 {
-        grid.step(&mut flashes);
+        match threads {
+            Some(threads) => grid.step_parallel(&mut flashes, threads),
+            None => grid.step(&mut flashes),
+        }
     }
 
     println!("{flashes}");
@@ -0,0 +1,229 @@
+//! stepping big grids on several threads at once.
+
+use std::{
+    num::NonZeroUsize,
+    sync::{Barrier, Mutex},
+    thread,
+};
+
+use log::trace;
+
+use crate::Grid;
+
+/// the number of cells below which `step` is used instead of `step_parallel`: a conservative threshold, since small grids step faster than threads can be spawned.
+pub const MIN_PARALLEL_CELLS: usize = 384 * 384;
+
+/// one thread's share of the grid: a band of whole rows.
+struct Band<'a> {
+    /// the index of the band's first row in the whole grid.
+    first_row: usize,
+    cols: usize,
+    cells: &'a mut [u8],
+    /// cells which have flashed already this step.
+    done: &'a mut [bool],
+}
+
+/// the columns of the cells in each row which are due to flash in the next wave.
+type Frontier = Vec<Vec<usize>>;
+
+impl Band<'_> {
+    fn rows(&self) -> usize {
+        self.cells.len() / self.cols
+    }
+
+    /// the increment at the start of the step. returns the band's part of the first wave.
+    fn increment(&mut self) -> Frontier {
+        let mut next = vec![Vec::new(); self.rows()];
+        for (i, cell) in self.cells.iter_mut().enumerate() {
+            *cell += 1;
+            if *cell > 9 {
+                next[i / self.cols].push(i % self.cols);
+            }
+        }
+        next
+    }
+
+    /// flash the band's part of `frontier`, and bump up every unflashed cell in the band next to anything in `frontier`. returns the band's part of the next wave.
+    fn wave(&mut self, frontier: &[Mutex<Vec<usize>>]) -> Frontier {
+        let rows = self.rows();
+        let cols = self.cols;
+        let band = self.first_row..self.first_row + rows;
+
+        for row in band.clone() {
+            for &col in lock(&frontier[row]).iter() {
+                let i = (row - self.first_row) * cols + col;
+                self.cells[i] = 0;
+                self.done[i] = true;
+            }
+        }
+
+        let mut next = vec![Vec::new(); rows];
+        // flashes in the rows just outside the band still reach into it.
+        let reach = band.start.saturating_sub(1)..(band.end + 1).min(frontier.len());
+        for row in reach {
+            for &col in lock(&frontier[row]).iter() {
+                let near_rows = row.saturating_sub(1).max(band.start)..(row + 2).min(band.end);
+                for r in near_rows {
+                    for c in col.saturating_sub(1)..(col + 2).min(cols) {
+                        let i = (r - self.first_row) * cols + c;
+                        if !self.done[i] {
+                            self.cells[i] += 1;
+                            // anything already over 9 is in `frontier`, and so done, so this can only happen once.
+                            if self.cells[i] == 10 {
+                                next[r - self.first_row].push(c);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        next
+    }
+}
+
+fn lock(row: &Mutex<Vec<usize>>) -> std::sync::MutexGuard<'_, Vec<usize>> {
+    row.lock().expect("stepping thread panicked")
+}
+
+impl Grid {
+    /// the same as `step`, but with the work split between `threads` threads, each of which takes a band of rows.
+    ///
+    /// flashes are handled in waves, as in `step_traced`: each wave, every thread flashes the cells in its band which went over 9 in the previous wave, and bumps up the unflashed cells in its band next to any of them, including flashes just across the edge of the band. the threads last the whole step, and wait for each other at the end of every wave.
+    pub fn step_parallel(&mut self, flashes: &mut usize, threads: NonZeroUsize) {
+        let (rows, cols) = self.dimensions();
+        if rows == 0 || cols == 0 {
+            return;
+        }
+        let band_rows = rows.div_ceil(threads.get());
+        let band_len = band_rows * cols;
+        let mut done = vec![false; rows * cols];
+
+        // the columns due to flash in each row, for this wave and the next. they swap over every wave, and each thread only writes to the rows in its own band.
+        let frontiers: [Vec<Mutex<Vec<usize>>>; 2] =
+            std::array::from_fn(|_| (0..rows).map(|_| Mutex::default()).collect());
+        let barrier = Barrier::new(rows.div_ceil(band_rows));
+
+        let (count, waves) = thread::scope(|s| {
+            let handles = self
+                .cells
+                .chunks_mut(band_len)
+                .zip(done.chunks_mut(band_len))
+                .enumerate()
+                .map(|(i, (cells, done))| {
+                    let (frontiers, barrier) = (&frontiers, &barrier);
+                    s.spawn(move || {
+                        let first_row = i * band_rows;
+                        let mut band = Band {
+                            first_row,
+                            cols,
+                            cells,
+                            done,
+                        };
+                        let publish = |frontier: &[Mutex<Vec<usize>>], part: Frontier| {
+                            for (row, part) in frontier[first_row..].iter().zip(part) {
+                                *lock(row) = part;
+                            }
+                        };
+
+                        publish(&frontiers[0], band.increment());
+                        let mut count = 0;
+                        let mut waves = 0;
+                        loop {
+                            // by now every thread has published its part of this wave.
+                            barrier.wait();
+                            let frontier = &frontiers[waves % 2];
+                            if frontier.iter().all(|row| lock(row).is_empty()) {
+                                break;
+                            }
+                            count += frontier[first_row..first_row + band.rows()]
+                                .iter()
+                                .map(|row| lock(row).len())
+                                .sum::<usize>();
+                            publish(&frontiers[(waves + 1) % 2], band.wave(frontier));
+                            waves += 1;
+                        }
+                        (count, waves)
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("stepping thread panicked"))
+                .fold((0, 0), |(total, _), (count, waves)| (total + count, waves))
+        });
+        *flashes += count;
+        trace!("{waves} waves of flashes");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::rng::Rng;
+
+    use super::*;
+
+    fn random_grid(rng: &mut Rng, rows: usize, cols: usize) -> Grid {
+        let mut grid = Grid::new(rows, cols);
+        for cell in grid.cells_mut() {
+            *cell = rng.below(10) as u8;
+        }
+        grid
+    }
+
+    #[test]
+    fn example() {
+        let input = include_str!("../../input/11e.txt");
+        let mut grid = crate::parse_input(&mut std::io::BufRead::lines(input.as_bytes())).unwrap();
+        let mut flashes = 0;
+        for _ in 0..100 {
+            grid.step_parallel(&mut flashes, NonZeroUsize::new(3).unwrap());
+        }
+        assert_eq!(flashes, 1656);
+    }
+
+    #[test]
+    fn differential() {
+        let mut rng = Rng::new(30);
+        let sizes = [
+            (1, 1),
+            (1, 17),
+            (17, 1),
+            (2, 2),
+            (10, 10),
+            (37, 5),
+            (64, 64),
+            (101, 77),
+        ];
+        for (rows, cols) in sizes {
+            for threads in [1, 2, 3, 8, 40] {
+                let threads = NonZeroUsize::new(threads).unwrap();
+                let mut serial = random_grid(&mut rng, rows, cols);
+                let mut parallel = serial.clone();
+                let (mut serial_flashes, mut parallel_flashes) = (0, 0);
+                for step in 0..20 {
+                    serial.step(&mut serial_flashes);
+                    parallel.step_parallel(&mut parallel_flashes, threads);
+                    assert_eq!(
+                        serial, parallel,
+                        "{rows}x{cols} on {threads} threads, step {step}"
+                    );
+                    assert_eq!(serial_flashes, parallel_flashes);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn big() {
+        let mut rng = Rng::new(4096);
+        let mut grid = random_grid(&mut rng, 512, 512);
+        let mut flashes = 0;
+        for _ in 0..5 {
+            grid.step_parallel(&mut flashes, NonZeroUsize::new(4).unwrap());
+        }
+        let zeros = grid.cells().iter().filter(|&&c| c == 0).count();
+        assert!(flashes >= zeros && zeros > 0);
+        assert!(grid.cells().iter().all(|&c| c <= 9));
+    }
+}
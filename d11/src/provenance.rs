@@ -0,0 +1,270 @@
+//! recording which flashes caused which during a step.
+
+use std::{collections::HashMap, fmt::Write};
+
+use log::trace;
+
+use crate::{Grid, Point};
+
+/// a single octopus flashing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Flash {
+    pub point: Point,
+    /// indices into `FlashTrace::flashes` of the neighbors whose flashes pushed this octopus over the edge. empty if it flashed just from the step's increment.
+    pub parents: Vec<usize>,
+    /// how many flashes deep into the cascade this flash is. flashes with no parents are at depth 0.
+    pub depth: usize,
+}
+
+/// the cascade of flashes during a single step, as a DAG.
+#[derive(Clone, Debug, Default)]
+pub struct FlashTrace {
+    /// all flashes in the step, ordered by depth and then by position.
+    pub flashes: Vec<Flash>,
+    index: HashMap<Point, usize>,
+}
+
+impl FlashTrace {
+    pub fn len(&self) -> usize {
+        self.flashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.flashes.is_empty()
+    }
+
+    /// the flash at `point`, if it flashed.
+    pub fn get(&self, point: Point) -> Option<&Flash> {
+        self.index.get(&point).map(|&i| &self.flashes[i])
+    }
+
+    /// the flashes which weren't caused by any other flash.
+    pub fn roots(&self) -> impl Iterator<Item = &Flash> {
+        self.flashes.iter().filter(|f| f.parents.is_empty())
+    }
+
+    /// the depth of the deepest flash, or `None` if nothing flashed.
+    pub fn max_depth(&self) -> Option<usize> {
+        self.flashes.last().map(|f| f.depth)
+    }
+
+    /// indices of the flashes which `i` directly helped cause.
+    pub fn children(&self, i: usize) -> impl Iterator<Item = usize> + '_ {
+        self.flashes
+            .iter()
+            .enumerate()
+            .skip(i + 1)
+            .filter(move |(_, f)| f.parents.contains(&i))
+            .map(|(j, _)| j)
+    }
+
+    /// the number of flashes which `i` contributed to, directly or indirectly, including itself.
+    pub fn cascade_size(&self, i: usize) -> usize {
+        let mut reached = vec![false; self.len()];
+        reached[i] = true;
+        // parents always come before their children, so one pass is enough.
+        for (j, flash) in self.flashes.iter().enumerate().skip(i + 1) {
+            reached[j] = flash.parents.iter().any(|&p| reached[p]);
+        }
+        reached.into_iter().filter(|&r| r).count()
+    }
+
+    /// the root whose cascade is largest, along with the size of that cascade. ties go to the earliest root.
+    pub fn largest_cascade_root(&self) -> Option<(Point, usize)> {
+        self.flashes
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| f.parents.is_empty())
+            .map(|(i, f)| (f.point, self.cascade_size(i)))
+            .fold(None, |best, (point, size)| match best {
+                Some((_, best_size)) if best_size >= size => best,
+                _ => Some((point, size)),
+            })
+    }
+
+    /// the points along the longest chain of flashes each causing the next, from a root to the deepest flash.
+    pub fn longest_chain(&self) -> Vec<Point> {
+        let mut chain = Vec::new();
+        let mut cur = self.flashes.len().checked_sub(1);
+        while let Some(i) = cur {
+            let flash = &self.flashes[i];
+            chain.push(flash.point);
+            cur = flash
+                .parents
+                .iter()
+                .copied()
+                .find(|&p| self.flashes[p].depth + 1 == flash.depth);
+        }
+        chain.reverse();
+        chain
+    }
+
+    /// render the DAG in Graphviz DOT format, with edges pointing from cause to effect.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph flashes {\n");
+        for flash in &self.flashes {
+            let Point { row, col } = flash.point;
+            writeln!(
+                out,
+                "    p{row}_{col} [label=\"{}\\ndepth {}\"];",
+                flash.point, flash.depth
+            )
+            .unwrap();
+        }
+        for flash in &self.flashes {
+            for &parent in &flash.parents {
+                let Point { row, col } = self.flashes[parent].point;
+                writeln!(
+                    out,
+                    "    p{row}_{col} -> p{}_{};",
+                    flash.point.row, flash.point.col
+                )
+                .unwrap();
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+impl Grid {
+    /// like `step`, but also record which flashes caused which.
+    ///
+    /// flashes are processed in waves: first everything that flashes from the step's increment, then everything pushed over the edge by those, and so on. the end result is the same as `step`.
+    pub fn step_traced(&mut self, flashes: &mut usize) -> FlashTrace {
+        let mut trace = FlashTrace::default();
+
+        let (rows, cols) = self.dimensions();
+        let mut wave = Vec::new();
+        for row in 0..rows {
+            for col in 0..cols {
+                self[row][col] += 1;
+                if self[row][col] > 9 {
+                    wave.push(Point::new(row, col));
+                }
+            }
+        }
+
+        let mut depth = 0;
+        while !wave.is_empty() {
+            trace!("wave {depth}: {} flashes", wave.len());
+            for &point in &wave {
+                let mut parents = self
+                    .neighbors(point)
+                    .filter_map(|n| trace.index.get(&n).copied())
+                    .filter(|&i| trace.flashes[i].depth < depth)
+                    .collect::<Vec<_>>();
+                parents.sort_unstable();
+                trace.index.insert(point, trace.flashes.len());
+                trace.flashes.push(Flash {
+                    point,
+                    parents,
+                    depth,
+                });
+                self[point.row][point.col] = 0;
+            }
+            *flashes += wave.len();
+
+            let mut next = Vec::new();
+            for point in wave {
+                for neighbor in self.neighbors(point).collect::<Vec<_>>() {
+                    if !trace.index.contains_key(&neighbor) {
+                        let energy = &mut self[neighbor.row][neighbor.col];
+                        *energy += 1;
+                        // it can only go from 9 to 10 once, so this queues it once however many neighbors push it.
+                        if *energy == 10 {
+                            next.push(neighbor);
+                        }
+                    }
+                }
+            }
+            next.sort_by_key(|p| (p.row, p.col));
+            wave = next;
+            depth += 1;
+        }
+
+        trace
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Grid {
+        crate::parse_input(&mut std::io::BufRead::lines(input.as_bytes())).unwrap()
+    }
+
+    #[test]
+    fn small_cascade() {
+        let mut grid = parse(
+            "1111100000\n1999100000\n1919100000\n1999100000\n1111100000\n\
+             0000000000\n0000000000\n0000000000\n0000000000\n0000000000\n",
+        );
+        let mut flashes = 0;
+        let trace = grid.step_traced(&mut flashes);
+        assert_eq!(flashes, 9);
+        assert_eq!(trace.len(), 9);
+        assert_eq!(trace.roots().count(), 8);
+        assert_eq!(trace.max_depth(), Some(1));
+
+        let center = trace.get(Point::new(2, 2)).unwrap();
+        assert_eq!(center.depth, 1);
+        assert_eq!(center.parents, (0..8).collect::<Vec<_>>());
+        assert_eq!(trace.children(0).collect::<Vec<_>>(), vec![8]);
+
+        assert_eq!(
+            trace.longest_chain(),
+            vec![Point::new(1, 1), Point::new(2, 2)]
+        );
+        assert_eq!(trace.largest_cascade_root(), Some((Point::new(1, 1), 2)));
+        assert_eq!(grid[0][..5], [3, 4, 5, 4, 3]);
+        assert_eq!(grid[2][..5], [5, 0, 0, 0, 5]);
+
+        let dot = trace.to_dot();
+        assert!(dot.starts_with("digraph flashes {\n"));
+        assert!(dot.contains("    p2_2 [label=\"(2, 2)\\ndepth 1\"];\n"));
+        assert!(dot.contains("    p1_1 -> p2_2;\n"));
+        assert_eq!(dot.matches(" -> ").count(), 8);
+    }
+
+    #[test]
+    fn chain() {
+        // each flash only just pushes the next one over.
+        let mut grid = parse(
+            "9800000000\n0080000000\n0006000000\n0000000000\n0000000000\n\
+             0000000000\n0000000000\n0000000000\n0000000000\n0000000000\n",
+        );
+        let trace = grid.step_traced(&mut 0);
+        assert_eq!(
+            trace.longest_chain(),
+            vec![Point::new(0, 0), Point::new(0, 1), Point::new(1, 2)]
+        );
+        assert_eq!(trace.largest_cascade_root(), Some((Point::new(0, 0), 3)));
+        assert!(trace.get(Point::new(2, 3)).is_none());
+    }
+
+    #[test]
+    fn matches_step() {
+        let input = include_str!("../../input/11e.txt");
+        let mut plain = parse(input);
+        let mut traced = parse(input);
+        let (mut plain_flashes, mut traced_flashes) = (0, 0);
+        for _ in 0..100 {
+            plain.step(&mut plain_flashes);
+            let before = traced_flashes;
+            let trace = traced.step_traced(&mut traced_flashes);
+            assert_eq!(plain, traced);
+            assert_eq!(trace.len(), traced_flashes - before);
+
+            for flash in &trace.flashes {
+                let parent_depth = flash.parents.iter().map(|&p| trace.flashes[p].depth).max();
+                assert_eq!(flash.depth, parent_depth.map_or(0, |d| d + 1));
+                for &p in &flash.parents {
+                    assert!(traced.neighbors(flash.point).any(|n| n == trace.flashes[p].point));
+                }
+            }
+        }
+        assert_eq!(traced_flashes, 1656);
+    }
+}
@@ -0,0 +1,248 @@
+//! animated terminal view of a `Grid` as it steps.
+
+use std::{
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+
+use crate::Grid;
+
+pub const CLEAR_SCREEN: &str = "\x1b[H\x1b[2J";
+
+const RESET: &str = "\x1b[0m";
+const FLASH: &str = "\x1b[1;30;103m";
+
+pub const MIN_DELAY: Duration = Duration::from_millis(10);
+pub const MAX_DELAY: Duration = Duration::from_millis(5000);
+pub const DEFAULT_DELAY: Duration = Duration::from_millis(200);
+
+/// the ANSI 256-colour code for a cell with the given energy level. energy fades from dark grey at 1 to white at 9.
+const fn energy_colour(energy: u8) -> u8 {
+    // the greyscale ramp runs from 232 (darkest) to 255 (lightest).
+    let energy = if energy > 9 { 9 } else { energy };
+    232 + energy * 2 + 5
+}
+
+/// render `grid` as a block of text, one line per row. cells with energy 0 flashed during the last step; when `colour` is set they are highlighted and every other cell is shaded by its energy, otherwise they are drawn as `*`.
+pub fn render(grid: &Grid, colour: bool) -> String {
+    let (rows, cols) = grid.dimensions();
+    let mut out = String::with_capacity(rows * (cols + 1));
+    for row in grid.rows() {
+        for &energy in row {
+            match (energy, colour) {
+                (0, false) => out.push('*'),
+                (n, false) => write!(out, "{n}").unwrap(),
+                (0, true) => write!(out, "{FLASH}0{RESET}").unwrap(),
+                (n, true) => write!(out, "\x1b[38;5;{}m{n}{RESET}", energy_colour(n)).unwrap(),
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// a user command for the viewer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Control {
+    /// advance a single step. only does anything while paused.
+    Step,
+    TogglePause,
+    Faster,
+    Slower,
+    Quit,
+}
+
+impl Control {
+    /// the control bound to a key, if any.
+    pub const fn from_key(key: u8) -> Option<Self> {
+        match key {
+            b'n' | b'.' => Some(Self::Step),
+            b' ' | b'p' => Some(Self::TogglePause),
+            b'+' | b'=' => Some(Self::Faster),
+            b'-' | b'_' => Some(Self::Slower),
+            b'q' | 0x1b => Some(Self::Quit),
+            _ => None,
+        }
+    }
+}
+
+/// the state of an animation: the grid, how far it has gotten, and how it is being played.
+pub struct Viewer {
+    pub grid: Grid,
+    pub steps: usize,
+    pub flashes: usize,
+    /// the number of flashes during the last step.
+    pub last_flashes: usize,
+    pub paused: bool,
+    pub delay: Duration,
+    pub colour: bool,
+}
+
+impl Viewer {
+    pub fn new(grid: Grid) -> Self {
+        Self {
+            grid,
+            steps: 0,
+            flashes: 0,
+            last_flashes: 0,
+            paused: false,
+            delay: DEFAULT_DELAY,
+            colour: true,
+        }
+    }
+
+    /// advance the grid by one step.
+    pub fn advance(&mut self) {
+        let before = self.flashes;
+        self.grid.step(&mut self.flashes);
+        self.last_flashes = self.flashes - before;
+        self.steps += 1;
+    }
+
+    /// apply `control`. returns `false` if the viewer should quit.
+    pub fn handle(&mut self, control: Control) -> bool {
+        match control {
+            Control::Step => {
+                if self.paused {
+                    self.advance();
+                }
+            }
+            Control::TogglePause => self.paused = !self.paused,
+            Control::Faster => self.delay = (self.delay / 2).max(MIN_DELAY),
+            Control::Slower => self.delay = (self.delay * 2).min(MAX_DELAY),
+            Control::Quit => return false,
+        }
+        true
+    }
+
+    /// the full frame for the current state: the grid and a status line.
+    pub fn frame(&self) -> String {
+        let mut out = render(&self.grid, self.colour);
+        writeln!(
+            out,
+            "step {} | flashes {} (+{}) | {}ms{}",
+            self.steps,
+            self.flashes,
+            self.last_flashes,
+            self.delay.as_millis(),
+            if self.paused { " | paused" } else { "" },
+        )
+        .unwrap();
+        out
+    }
+}
+
+/// the path of the `n`th captured frame in `dir`.
+pub fn frame_path(dir: &Path, n: usize) -> PathBuf {
+    dir.join(format!("frame_{n:04}.txt"))
+}
+
+/// run `viewer` for `steps` steps without a terminal, writing the initial frame and the frame after every step to numbered text files in `dir`. returns the paths written, in order.
+pub fn capture(viewer: &mut Viewer, steps: usize, dir: &Path) -> Result<Vec<PathBuf>> {
+    fs::create_dir_all(dir).context(format!("couldn't create {}", dir.display()))?;
+    let mut paths = Vec::with_capacity(steps + 1);
+    for n in 0..=steps {
+        if n > 0 {
+            viewer.advance();
+        }
+        let path = frame_path(dir, n);
+        fs::write(&path, viewer.frame()).context(format!("couldn't write {}", path.display()))?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> Grid {
+        let input = include_str!("../../input/11e.txt");
+        crate::parse_input(&mut std::io::BufRead::lines(input.as_bytes())).unwrap()
+    }
+
+    #[test]
+    fn render_plain() {
+        let mut grid = example();
+        let mut flashes = 0;
+        grid.step(&mut flashes);
+        grid.step(&mut flashes);
+        assert_eq!(
+            render(&grid, false),
+            "88*7476555\n\
+             5*89*87*54\n\
+             85978896*8\n\
+             84857696**\n\
+             87**9*88**\n\
+             66***88989\n\
+             68****5943\n\
+             ******7456\n\
+             9******876\n\
+             87****6848\n"
+        );
+    }
+
+    #[test]
+    fn render_colour() {
+        let mut grid = Grid::new(10, 10);
+        grid[0][0] = 9;
+        let out = render(&grid, true);
+        assert!(out.starts_with("\x1b[38;5;255m9\x1b[0m"));
+        assert_eq!(out.matches(FLASH).count(), 10 * 10 - 1);
+    }
+
+    #[test]
+    fn controls() {
+        let mut viewer = Viewer::new(example());
+        assert!(viewer.handle(Control::Step));
+        assert_eq!(
+            viewer.steps, 0,
+            "stepping shouldn't do anything while playing"
+        );
+
+        viewer.handle(Control::TogglePause);
+        viewer.handle(Control::Step);
+        viewer.handle(Control::Step);
+        assert_eq!(
+            (viewer.steps, viewer.flashes, viewer.last_flashes),
+            (2, 35, 35)
+        );
+
+        for _ in 0..20 {
+            viewer.handle(Control::Faster);
+        }
+        assert_eq!(viewer.delay, MIN_DELAY);
+        for _ in 0..20 {
+            viewer.handle(Control::Slower);
+        }
+        assert_eq!(viewer.delay, MAX_DELAY);
+
+        assert!(!viewer.handle(Control::Quit));
+        assert_eq!(Control::from_key(b' '), Some(Control::TogglePause));
+        assert_eq!(Control::from_key(b'x'), None);
+    }
+
+    #[test]
+    fn capture_frames() {
+        let dir = std::env::temp_dir().join(format!("d11-capture-{}", std::process::id()));
+        let mut viewer = Viewer::new(example());
+        viewer.colour = false;
+        let paths = capture(&mut viewer, 10, &dir).unwrap();
+        assert_eq!(paths.len(), 11);
+        assert_eq!(paths[3], frame_path(&dir, 3));
+
+        let first = fs::read_to_string(&paths[0]).unwrap();
+        assert!(first.starts_with("5483143223\n"));
+        assert!(first.ends_with("step 0 | flashes 0 (+0) | 200ms\n"));
+
+        let last = fs::read_to_string(&paths[10]).unwrap();
+        assert_eq!(last, viewer.frame());
+        assert!(last.ends_with("step 10 | flashes 204 (+29) | 200ms\n"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
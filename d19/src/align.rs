@@ -0,0 +1,293 @@
+//! putting every scanner into the reference frame of the first one.
+//!
+//! scanners are referred to by their index in the input throughout, not by their `Scanner::id`: ids can come in any order, so the first scanner isn't necessarily scanner 0.
+
+use std::{collections::VecDeque, fmt::Display};
+
+use log::trace;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    distance::{self, Metric, Pair},
+    AlignConfig, BeaconMap, Displacement, Fingerprint, Point, Rotation, Scanner,
+};
+
+/// where a scanner is and which way it's facing, relative to the first scanner in the input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "Point<N>: Serialize",
+    deserialize = "Point<N>: Deserialize<'de>"
+))]
+pub struct Pose<const N: usize = 3> {
+    pub rotation: Rotation<N>,
+    pub position: Point<N>,
+}
+
+impl<const N: usize> Display for Pose<N> {
+    /// e.g. `68,-1246,-43 -x+y-z`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.position, self.rotation)
+    }
+}
+
+impl<const N: usize> Pose<N> {
+    /// the pose of the first scanner.
+    pub const ORIGIN: Self = Self {
+        rotation: Rotation::IDENTITY,
+        position: Point::ORIGIN,
+    };
+
+    /// turn a point seen by this scanner into a point relative to the first scanner.
+    pub fn apply(&self, pt: Point<N>) -> Point<N> {
+        self.rotation.apply(pt) + (self.position - Point::ORIGIN)
+    }
+
+    /// the pose of a scanner which `diff`s from a scanner with this pose by `rot` and `dsp`.
+    pub fn then(&self, rot: &Rotation<N>, dsp: Displacement<N>) -> Self {
+        Self {
+            rotation: self.rotation.compose(rot),
+            position: self.position + self.rotation.apply_dsp(dsp),
+        }
+    }
+}
+
+/// a pair of scanners, by input index, which were found to overlap: `rotation` and `displacement` take points seen by `other` into the reference frame of `base`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Match<const N: usize = 3> {
+    pub base: usize,
+    pub other: usize,
+    pub rotation: Rotation<N>,
+    pub displacement: Displacement<N>,
+}
+
+impl<const N: usize> Match<N> {
+    /// the same match, the other way around.
+    #[must_use]
+    pub fn inverse(&self) -> Self {
+        let rotation = self.rotation.inverse();
+        Self {
+            base: self.other,
+            other: self.base,
+            rotation,
+            displacement: -rotation.apply_dsp(self.displacement),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Alignment<const N: usize = 3> {
+    /// the pose of each scanner, by input index. `None` if it couldn't be matched up with anything connected to the first scanner.
+    pub poses: Vec<Option<Pose<N>>>,
+    /// every beacon seen by a placed scanner, relative to the first scanner, without duplicates.
+    pub beacons: Vec<Point<N>>,
+    /// the matches which the poses were worked out from. each placed scanner other than the first is the `other` of exactly one of these.
+    pub matches: Vec<Match<N>>,
+}
+
+impl<const N: usize> Alignment<N> {
+    /// the positions of all of the placed scanners.
+    pub fn positions(&self) -> impl Iterator<Item = Point<N>> + '_ {
+        self.poses.iter().flatten().map(|pose| pose.position)
+    }
+
+    /// the input indices of the scanners which couldn't be placed. use `Scanner::id` to turn them into ids.
+    pub fn unreachable(&self) -> Vec<usize> {
+        (0..self.poses.len())
+            .filter(|&i| self.poses[i].is_none())
+            .collect()
+    }
+
+    /// the greatest taxicab distance between any two placed scanners, or 0 if fewer than two are placed. see `distance::farthest_pair`.
+    pub fn max_distance(&self) -> u64 {
+        self.farthest_pair(Metric::Taxicab)
+            .map_or(0, |pair| pair.distance)
+    }
+
+    /// the indices and positions of the placed scanners.
+    fn placed(&self) -> (Vec<usize>, Vec<Point<N>>) {
+        self.poses
+            .iter()
+            .enumerate()
+            .filter_map(|(i, pose)| Some((i, pose.as_ref()?.position)))
+            .unzip()
+    }
+
+    /// the distance between scanners `a` and `b`, by input index, if they're both placed.
+    pub fn distance(&self, a: usize, b: usize, metric: Metric) -> Option<u64> {
+        let (a, b) = (self.poses.get(a)?.as_ref()?, self.poses.get(b)?.as_ref()?);
+        Some(metric.dist(&a.position, &b.position))
+    }
+
+    /// the distance between every pair of scanners, by input index. entries are `None` if either scanner wasn't placed.
+    pub fn distance_matrix(&self, metric: Metric) -> Vec<Vec<Option<u64>>> {
+        (0..self.poses.len())
+            .map(|a| {
+                (0..self.poses.len())
+                    .map(|b| self.distance(a, b, metric))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// the two placed scanners closest together, by input index. see `distance::nearest_pair`.
+    pub fn nearest_pair(&self, metric: Metric) -> Option<Pair> {
+        let (indices, positions) = self.placed();
+        distance::nearest_pair(&positions, metric).map(|pair| Pair {
+            a: indices[pair.a],
+            b: indices[pair.b],
+            ..pair
+        })
+    }
+
+    /// two placed scanners which are as far apart as any, by input index. see `distance::farthest_pair`.
+    pub fn farthest_pair(&self, metric: Metric) -> Option<Pair> {
+        let (indices, positions) = self.placed();
+        distance::farthest_pair(&positions, metric).map(|pair| Pair {
+            a: indices[pair.a],
+            b: indices[pair.b],
+            ..pair
+        })
+    }
+}
+
+/// work out where every scanner is relative to the first scanner in `scanners`, starting from it and spreading out through scanners which overlap ones that have already been placed. pairs whose `Fingerprint`s rule out an overlap are skipped without running `diff`.
+pub fn align<const N: usize>(scanners: &[Scanner<N>]) -> Alignment<N> {
+    align_with(scanners, &AlignConfig::default())
+}
+
+/// the same as `align`, but matching scanners with `Scanner::diff_with` and `config`.
+pub fn align_with<const N: usize>(scanners: &[Scanner<N>], config: &AlignConfig) -> Alignment<N> {
+    let mut res = Alignment {
+        poses: vec![None; scanners.len()],
+        ..Alignment::default()
+    };
+    if scanners.is_empty() {
+        return res;
+    }
+
+    let fingerprints = scanners.iter().map(Fingerprint::new).collect::<Vec<_>>();
+
+    res.poses[0] = Some(Pose::ORIGIN);
+    let mut queue = VecDeque::from([0]);
+    while let Some(base) = queue.pop_front() {
+        let pose = res.poses[base].expect("queued scanners are placed");
+        for other in 0..scanners.len() {
+            if res.poses[other].is_some() {
+                continue;
+            }
+            if !fingerprints[base].plausible(&fingerprints[other], config.min_overlap) {
+                continue;
+            }
+            trace!("checking scanner {base} against scanner {other}");
+            if let Some((rotation, displacement)) =
+                scanners[base].diff_with(&scanners[other], config)
+            {
+                trace!(
+                    "success! scanner {other} has displacement {displacement} to scanner {base}"
+                );
+                res.poses[other] = Some(pose.then(&rotation, displacement));
+                res.matches.push(Match {
+                    base,
+                    other,
+                    rotation,
+                    displacement,
+                });
+                queue.push_back(other);
+            }
+        }
+    }
+
+    res.beacons = merge_beacons(scanners, &res.poses);
+    res
+}
+
+/// every beacon seen by a scanner with a pose, moved into the first scanner's reference frame, without duplicates.
+pub(crate) fn merge_beacons<const N: usize>(
+    scanners: &[Scanner<N>],
+    poses: &[Option<Pose<N>>],
+) -> Vec<Point<N>> {
+    let mut map = BeaconMap::new();
+    for (scanner, pose) in scanners.iter().zip(poses) {
+        if let Some(pose) = pose {
+            map.add_scanner(scanner, pose);
+        }
+    }
+    map.into_beacons()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn example() {
+        let scanners = crate::tests::example();
+        let alignment = align(&scanners);
+        assert!(alignment.poses.iter().all(Option::is_some));
+        assert_eq!(alignment.beacons.len(), 79);
+        assert_eq!(alignment.max_distance(), 3621);
+        assert_eq!(alignment.matches.len(), scanners.len() - 1);
+        assert_eq!(
+            alignment.poses[2].unwrap().position,
+            Point::new(1105, -1205, 1229)
+        );
+        assert_eq!(
+            alignment.poses[4].unwrap().position,
+            Point::new(-20, -1133, 1061)
+        );
+
+        // the farthest apart are 2 and 3, and the closest are 1 and 4 whatever the metric.
+        assert_eq!(
+            alignment.farthest_pair(Metric::Taxicab),
+            Some(Pair {
+                a: 2,
+                b: 3,
+                distance: 3621
+            })
+        );
+        for metric in Metric::ALL {
+            let nearest = alignment.nearest_pair(metric).unwrap();
+            assert_eq!((nearest.a, nearest.b), (1, 4), "{metric:?}");
+            let matrix = alignment.distance_matrix(metric);
+            assert_eq!(matrix[1][4], Some(nearest.distance));
+            assert_eq!(matrix[4][1], Some(nearest.distance));
+            assert_eq!(matrix[0][0], Some(0));
+        }
+        assert_eq!(
+            alignment.distance(1, 4, Metric::Taxicab),
+            Some(88 + 113 + 1104)
+        );
+
+        // every beacon a scanner sees ends up in the merged set.
+        for (scanner, pose) in scanners.iter().zip(&alignment.poses) {
+            for &beacon in &scanner.beacons {
+                assert!(alignment.beacons.contains(&pose.unwrap().apply(beacon)));
+            }
+        }
+    }
+
+    #[test]
+    fn unreachable() {
+        let mut scanners = crate::tests::example();
+        // a scanner which sees nothing in common with anyone else.
+        scanners.push(Scanner::new(
+            5,
+            vec![Point::new(1, 2, 3), Point::new(4, 5, 6)],
+        ));
+        let alignment = align(&scanners);
+        assert_eq!(alignment.poses[5], None);
+        assert_eq!(alignment.beacons.len(), 79);
+        assert_eq!(alignment.max_distance(), 3621);
+        assert_eq!(alignment.distance(5, 0, Metric::Taxicab), None);
+        let matrix = alignment.distance_matrix(Metric::Chebyshev);
+        assert!(matrix[5].iter().all(Option::is_none));
+        assert!(matrix.iter().all(|row| row[5].is_none()));
+        assert_eq!(alignment.farthest_pair(Metric::Taxicab).unwrap().b, 3);
+
+        assert_eq!(align::<3>(&[]).beacons.len(), 0);
+        let alone = align(&scanners[..1]);
+        assert_eq!(alone.max_distance(), 0);
+        assert_eq!(alone.nearest_pair(Metric::Euclidean), None);
+    }
+}
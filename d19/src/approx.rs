@@ -0,0 +1,608 @@
+//! matching scanners whose readings aren't exact, by finding the rigid transform which lines up the most beacons to within a tolerance.
+//!
+//! candidate transforms come from RANSAC: pick three beacons from one scanner, look up pairs in the other with about the same distance as the first two, complete each to a triangle of the same shape, and fit a transform to those. the best candidate is then refined by least squares over all of the beacons it lines up.
+//!
+//! unlike the exact matcher, this only works in 3 dimensions, and it merges the beacons of an alignment by comparing each with every one found so far, which is quadratic in the number of beacons. that's fine for puzzle-sized inputs, but not for big generated worlds.
+
+use std::{collections::VecDeque, fmt::Display};
+
+use common::rng::Rng;
+
+use log::trace;
+
+use crate::{Point, Rotation, Scanner, MIN_OVERLAP};
+
+pub type Vector = [f64; 3];
+pub type Matrix = [[f64; 3]; 3];
+
+fn vector(pt: Point) -> Vector {
+    pt.coords().map(f64::from)
+}
+
+fn sub(a: Vector, b: Vector) -> Vector {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dist(a: Vector, b: Vector) -> f64 {
+    let d = sub(a, b);
+    (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt()
+}
+
+fn mul(m: &Matrix, v: Vector) -> Vector {
+    [0, 1, 2].map(|row| m[row][0] * v[0] + m[row][1] * v[1] + m[row][2] * v[2])
+}
+
+/// a rotation by any angle, and then a translation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RigidTransform {
+    pub rotation: Matrix,
+    pub translation: Vector,
+}
+
+impl Display for RigidTransform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let [x, y, z] = self.translation;
+        write!(f, "{x:.1},{y:.1},{z:.1} {}", self.nearest_rotation())
+    }
+}
+
+impl RigidTransform {
+    pub const IDENTITY: Self = Self {
+        rotation: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+        translation: [0.0; 3],
+    };
+
+    pub fn apply(&self, v: Vector) -> Vector {
+        let r = mul(&self.rotation, v);
+        [
+            r[0] + self.translation[0],
+            r[1] + self.translation[1],
+            r[2] + self.translation[2],
+        ]
+    }
+
+    /// the transform which does `other` and then `self`.
+    #[must_use]
+    pub fn compose(&self, other: &Self) -> Self {
+        let (a, b) = (self.rotation, other.rotation);
+        let rotation = [0, 1, 2].map(|row| {
+            [0, 1, 2]
+                .map(|col| a[row][0] * b[0][col] + a[row][1] * b[1][col] + a[row][2] * b[2][col])
+        });
+        Self {
+            rotation,
+            translation: self.apply(other.translation),
+        }
+    }
+
+    /// the one of the 24 axis-aligned rotations which is closest to this one.
+    pub fn nearest_rotation(&self) -> Rotation {
+        let score = |rot: &Rotation| {
+            let m = rot.matrix();
+            let mut sum = 0.0;
+            for (row, m_row) in m.iter().enumerate() {
+                for (col, &n) in m_row.iter().enumerate() {
+                    sum += f64::from(n) * self.rotation[row][col];
+                }
+            }
+            sum
+        };
+        Rotation::ALL
+            .into_iter()
+            .max_by(|a, b| score(a).total_cmp(&score(b)))
+            .expect("there are 24 rotations")
+    }
+
+    /// the rigid transform which takes each `from` as close as possible to the `to` paired with it, in the least squares sense. this is Horn's quaternion method, which gives the same answer as the Kabsch algorithm without needing an SVD. `None` if there are fewer than 3 pairs.
+    pub fn fit(pairs: &[(Vector, Vector)]) -> Option<Self> {
+        if pairs.len() < 3 {
+            return None;
+        }
+        let n = pairs.len() as f64;
+        let mut from_mean = [0.0; 3];
+        let mut to_mean = [0.0; 3];
+        for (from, to) in pairs {
+            for i in 0..3 {
+                from_mean[i] += from[i] / n;
+                to_mean[i] += to[i] / n;
+            }
+        }
+
+        // the cross-covariance matrix.
+        let mut s = [[0.0; 3]; 3];
+        for &(from, to) in pairs {
+            let (p, q) = (sub(from, from_mean), sub(to, to_mean));
+            for a in 0..3 {
+                for b in 0..3 {
+                    s[a][b] += p[a] * q[b];
+                }
+            }
+        }
+        let [[sxx, sxy, sxz], [syx, syy, syz], [szx, szy, szz]] = s;
+        let n = [
+            [sxx + syy + szz, syz - szy, szx - sxz, sxy - syx],
+            [syz - szy, sxx - syy - szz, sxy + syx, szx + sxz],
+            [szx - sxz, sxy + syx, -sxx + syy - szz, syz + szy],
+            [sxy - syx, szx + sxz, syz + szy, -sxx - syy + szz],
+        ];
+
+        // the best rotation is the unit quaternion along the eigenvector with the largest eigenvalue.
+        let (values, vectors) = jacobi(n);
+        let best = (0..4)
+            .max_by(|&a, &b| values[a].total_cmp(&values[b]))
+            .expect("4 eigenvalues");
+        let [w, x, y, z] = [0, 1, 2, 3].map(|i| vectors[i][best]);
+        let rotation = [
+            [
+                w * w + x * x - y * y - z * z,
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+            ],
+            [
+                2.0 * (x * y + w * z),
+                w * w - x * x + y * y - z * z,
+                2.0 * (y * z - w * x),
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                w * w - x * x - y * y + z * z,
+            ],
+        ];
+        let rotated = mul(&rotation, from_mean);
+        Some(Self {
+            rotation,
+            translation: sub(to_mean, rotated),
+        })
+    }
+}
+
+/// the eigenvalues and eigenvectors (as columns) of a symmetric 4x4 matrix, by cyclic Jacobi rotations.
+fn jacobi(mut a: [[f64; 4]; 4]) -> ([f64; 4], [[f64; 4]; 4]) {
+    let mut v = [[0.0; 4]; 4];
+    for (i, row) in v.iter_mut().enumerate() {
+        row[i] = 1.0;
+    }
+
+    for _ in 0..50 {
+        let off = (0..4)
+            .flat_map(|p| (p + 1..4).map(move |q| (p, q)))
+            .map(|(p, q)| a[p][q] * a[p][q])
+            .sum::<f64>();
+        if off < 1e-22 {
+            break;
+        }
+        for p in 0..4 {
+            for q in p + 1..4 {
+                if a[p][q].abs() < 1e-300 {
+                    continue;
+                }
+                // the rotation in the p-q plane which zeroes a[p][q].
+                let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let t = if theta == 0.0 { 1.0 } else { t };
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                for row in &mut a {
+                    let (akp, akq) = (row[p], row[q]);
+                    row[p] = c * akp - s * akq;
+                    row[q] = s * akp + c * akq;
+                }
+                let (row_p, row_q) = (a[p], a[q]);
+                for k in 0..4 {
+                    a[p][k] = c * row_p[k] - s * row_q[k];
+                    a[q][k] = s * row_p[k] + c * row_q[k];
+                }
+                for row in &mut v {
+                    let (vkp, vkq) = (row[p], row[q]);
+                    row[p] = c * vkp - s * vkq;
+                    row[q] = s * vkp + c * vkq;
+                }
+            }
+        }
+    }
+
+    ([a[0][0], a[1][1], a[2][2], a[3][3]], v)
+}
+
+/// how loosely to match.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ApproxConfig {
+    /// how far apart two sightings can be and still be the same beacon.
+    pub tolerance: f64,
+    /// how many beacons two scanners must agree on.
+    pub min_overlap: usize,
+    /// how many random triples of beacons to try per pair of scanners. each costs a lookup in the other scanner's distances, plus a fit for every matching triangle found there.
+    pub iterations: usize,
+    pub seed: u64,
+}
+
+impl Default for ApproxConfig {
+    fn default() -> Self {
+        Self {
+            tolerance: 10.0,
+            min_overlap: MIN_OVERLAP,
+            iterations: 300,
+            seed: 41,
+        }
+    }
+}
+
+/// an approximate match between two scanners.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApproxMatch {
+    /// takes points seen by the other scanner into this scanner's frame.
+    pub transform: RigidTransform,
+    /// the pairs of beacons which line up, as `(index in this scanner, index in the other)`.
+    pub inliers: Vec<(usize, usize)>,
+    /// the root mean square distance between the beacons in each pair of `inliers`, after transforming.
+    pub residual: f64,
+}
+
+/// every pair of a scanner's beacons as `(distance, i, j)` with `i < j`, sorted by distance, for finding pairs about as far apart as some other pair.
+struct Distances(Vec<(f64, usize, usize)>);
+
+impl Distances {
+    fn new(beacons: &[Vector]) -> Self {
+        let mut pairs = Vec::with_capacity(beacons.len() * beacons.len().saturating_sub(1) / 2);
+        for i in 0..beacons.len() {
+            for j in i + 1..beacons.len() {
+                pairs.push((dist(beacons[i], beacons[j]), i, j));
+            }
+        }
+        pairs.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self(pairs)
+    }
+
+    /// the pairs whose distance is within `slack` of `d`.
+    fn near(&self, d: f64, slack: f64) -> &[(f64, usize, usize)] {
+        let start = self.0.partition_point(|p| p.0 < d - slack);
+        let end = self.0.partition_point(|p| p.0 <= d + slack);
+        &self.0[start..end]
+    }
+}
+
+/// pair up each of `other`'s beacons, moved by `transform`, with the nearest of `mine` within `tolerance`, with no beacon used twice.
+fn inliers(
+    mine: &[Vector],
+    other: &[Vector],
+    transform: &RigidTransform,
+    tolerance: f64,
+) -> Vec<(usize, usize)> {
+    let mut used = vec![false; mine.len()];
+    let mut res = Vec::new();
+    for (j, &b) in other.iter().enumerate() {
+        let moved = transform.apply(b);
+        let nearest = (0..mine.len())
+            .filter(|&i| !used[i])
+            .map(|i| (i, dist(mine[i], moved)))
+            .filter(|&(_, d)| d <= tolerance)
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((i, _)) = nearest {
+            used[i] = true;
+            res.push((i, j));
+        }
+    }
+    res
+}
+
+fn residual(
+    mine: &[Vector],
+    other: &[Vector],
+    transform: &RigidTransform,
+    pairs: &[(usize, usize)],
+) -> f64 {
+    let sum = pairs
+        .iter()
+        .map(|&(i, j)| dist(mine[i], transform.apply(other[j])).powi(2))
+        .sum::<f64>();
+    (sum / pairs.len().max(1) as f64).sqrt()
+}
+
+impl Scanner {
+    /// the approximate version of `diff`: a transform which lines up at least `config.min_overlap` of `other`'s beacons with `self`'s to within `config.tolerance`, if RANSAC can find one.
+    pub fn diff_approx(&self, other: &Self, config: &ApproxConfig) -> Option<ApproxMatch> {
+        let mine = self.beacons.iter().map(|&b| vector(b)).collect::<Vec<_>>();
+        let theirs = other.beacons.iter().map(|&b| vector(b)).collect::<Vec<_>>();
+        if mine.len() < 3 || theirs.len() < 3 {
+            return None;
+        }
+        // two sightings of the same pair of beacons can disagree about the distance between them by up to twice the tolerance.
+        let slack = 2.0 * config.tolerance;
+        let close = |a: f64, b: f64| (a - b).abs() <= slack;
+        let distances = Distances::new(&theirs);
+
+        let mut rng = Rng::new(config.seed);
+        let mut best: Option<(RigidTransform, Vec<(usize, usize)>)> = None;
+        for _ in 0..config.iterations {
+            let a = rng.index(mine.len());
+            let b = rng.index(mine.len());
+            let c = rng.index(mine.len());
+            if a == b || b == c || a == c {
+                continue;
+            }
+            let (ab, bc, ac) = (
+                dist(mine[a], mine[b]),
+                dist(mine[b], mine[c]),
+                dist(mine[a], mine[c]),
+            );
+
+            // the pairs which could be `a` and `b`, either way round, and then the beacons which could be `c` for each.
+            for &(_, i, j) in distances.near(ab, slack) {
+                for (x, y) in [(i, j), (j, i)] {
+                    for z in 0..theirs.len() {
+                        if z == x
+                            || z == y
+                            || !close(bc, dist(theirs[y], theirs[z]))
+                            || !close(ac, dist(theirs[x], theirs[z]))
+                        {
+                            continue;
+                        }
+                        let Some(transform) = RigidTransform::fit(&[
+                            (theirs[x], mine[a]),
+                            (theirs[y], mine[b]),
+                            (theirs[z], mine[c]),
+                        ]) else {
+                            continue;
+                        };
+                        let found = inliers(&mine, &theirs, &transform, config.tolerance);
+                        if best.as_ref().is_none_or(|(_, b)| found.len() > b.len()) {
+                            best = Some((transform, found));
+                        }
+                    }
+                }
+            }
+
+            if best
+                .as_ref()
+                .is_some_and(|(_, b)| b.len() >= config.min_overlap)
+            {
+                break;
+            }
+        }
+
+        let (transform, found) = best?;
+        if found.len() < config.min_overlap {
+            return None;
+        }
+
+        // refit to all of the inliers, which might line up a few more.
+        let pairs = found
+            .iter()
+            .map(|&(i, j)| (theirs[j], mine[i]))
+            .collect::<Vec<_>>();
+        let refined = RigidTransform::fit(&pairs).unwrap_or(transform);
+        let refound = inliers(&mine, &theirs, &refined, config.tolerance);
+        let (transform, found) = if refound.len() >= found.len() {
+            (refined, refound)
+        } else {
+            (transform, found)
+        };
+
+        Some(ApproxMatch {
+            residual: residual(&mine, &theirs, &transform, &found),
+            transform,
+            inliers: found,
+        })
+    }
+}
+
+/// an approximate match between two scanners found by `align_approx`, which takes points seen by `other` into `base`'s frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApproxPair {
+    pub base: usize,
+    pub other: usize,
+    pub found: ApproxMatch,
+}
+
+/// the approximate version of `Alignment`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ApproxAlignment {
+    /// the transform from each scanner's frame into the first scanner's, by input index, if it could be placed.
+    pub poses: Vec<Option<RigidTransform>>,
+    /// every beacon, relative to the first scanner. sightings within the tolerance of one already found are taken to be the same beacon, and left out.
+    pub beacons: Vec<Vector>,
+    /// the matches which the poses were worked out from, with their residuals.
+    pub matches: Vec<ApproxPair>,
+}
+
+/// the approximate version of `align`: spread out from the first scanner with `diff_approx`.
+pub fn align_approx(scanners: &[Scanner], config: &ApproxConfig) -> ApproxAlignment {
+    let mut res = ApproxAlignment {
+        poses: vec![None; scanners.len()],
+        ..ApproxAlignment::default()
+    };
+    if scanners.is_empty() {
+        return res;
+    }
+
+    res.poses[0] = Some(RigidTransform::IDENTITY);
+    let mut queue = VecDeque::from([0]);
+    while let Some(base) = queue.pop_front() {
+        let pose = res.poses[base].expect("queued scanners are placed");
+        for other in 0..scanners.len() {
+            if res.poses[other].is_some() {
+                continue;
+            }
+            if let Some(found) = scanners[base].diff_approx(&scanners[other], config) {
+                trace!(
+                    "scanner {other} is at {} from scanner {base}, residual {:.2}",
+                    found.transform,
+                    found.residual
+                );
+                res.poses[other] = Some(pose.compose(&found.transform));
+                res.matches.push(ApproxPair { base, other, found });
+                queue.push_back(other);
+            }
+        }
+    }
+
+    for (scanner, pose) in scanners.iter().zip(&res.poses) {
+        let Some(pose) = pose else { continue };
+        for &beacon in &scanner.beacons {
+            let beacon = pose.apply(vector(beacon));
+            if !res
+                .beacons
+                .iter()
+                .any(|&b| dist(b, beacon) <= config.tolerance)
+            {
+                res.beacons.push(beacon);
+            }
+        }
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gen::{World, WorldConfig};
+
+    fn close(a: Vector, b: Vector, tolerance: f64) -> bool {
+        dist(a, b) <= tolerance
+    }
+
+    /// a rotation matrix from an arbitrary (not necessarily unit) quaternion.
+    fn quaternion(q: [f64; 4]) -> Matrix {
+        let len = q.iter().map(|n| n * n).sum::<f64>().sqrt();
+        let [w, x, y, z] = q.map(|n| n / len);
+        [
+            [
+                w * w + x * x - y * y - z * z,
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+            ],
+            [
+                2.0 * (x * y + w * z),
+                w * w - x * x + y * y - z * z,
+                2.0 * (y * z - w * x),
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                w * w - x * x - y * y + z * z,
+            ],
+        ]
+    }
+
+    #[test]
+    fn fit() {
+        let mut rng = Rng::new(410);
+        for _ in 0..100 {
+            let truth = RigidTransform {
+                rotation: quaternion([0; 4].map(|_| rng.normal())),
+                translation: [0; 3].map(|_| rng.range(-2000..2000) as f64),
+            };
+            let pairs = (0..rng.range(3..20))
+                .map(|_| {
+                    let from = [0; 3].map(|_| rng.range(-1000..1000) as f64);
+                    (from, truth.apply(from))
+                })
+                .collect::<Vec<_>>();
+            let found = RigidTransform::fit(&pairs).unwrap();
+            for &(from, to) in &pairs {
+                assert!(close(found.apply(from), to, 1e-6), "{found:?} vs {truth:?}");
+            }
+        }
+
+        // and the axis-aligned ones come back as themselves.
+        for rot in Rotation::ALL {
+            let pts = [
+                Point::new(1, 2, 3),
+                Point::new(-40, 5, 9),
+                Point::new(7, -7, 100),
+                Point::new(0, 0, 0),
+            ];
+            let pairs = pts.map(|p| (vector(p), vector(rot.apply(p))));
+            assert_eq!(RigidTransform::fit(&pairs).unwrap().nearest_rotation(), rot);
+        }
+
+        assert!(RigidTransform::fit(&[([0.0; 3], [1.0; 3])]).is_none());
+    }
+
+    #[test]
+    fn exact_example() {
+        // with no noise at all, the approximate matcher agrees with the exact one.
+        let scanners = crate::tests::example();
+        let exact = crate::align(&scanners);
+        let approx = align_approx(&scanners, &ApproxConfig::default());
+        assert_eq!(approx.beacons.len(), 79);
+        for (exact, approx) in exact.poses.iter().zip(&approx.poses) {
+            let (exact, approx) = (exact.unwrap(), approx.unwrap());
+            assert_eq!(approx.nearest_rotation(), exact.rotation);
+            assert!(close(approx.translation, vector(exact.position), 1e-6));
+        }
+        assert!(approx.matches.iter().all(|m| m.found.residual < 1e-6));
+    }
+
+    #[test]
+    fn noisy() {
+        let world_config = WorldConfig {
+            seed: 4,
+            scanners: 8,
+            noise: 3,
+            ..WorldConfig::default()
+        };
+        let world: World = World::generate(&world_config).unwrap();
+        // the exact matcher can't cope.
+        assert!(crate::align(&world.scanners)
+            .poses
+            .iter()
+            .any(Option::is_none));
+
+        let config = ApproxConfig {
+            tolerance: 12.0,
+            ..ApproxConfig::default()
+        };
+        let alignment = align_approx(&world.scanners, &config);
+        for (found, truth) in alignment.poses.iter().zip(&world.poses) {
+            let found = found.expect("every scanner placed");
+            assert_eq!(found.nearest_rotation(), truth.rotation);
+            // errors add up a little along each chain of matches.
+            assert!(
+                close(found.translation, vector(truth.position), 15.0),
+                "{found} vs {truth}"
+            );
+        }
+        for m in &alignment.matches {
+            assert!(m.found.inliers.len() >= config.min_overlap);
+            assert!(
+                m.found.residual > 0.0 && m.found.residual < 8.0,
+                "{}",
+                m.found.residual
+            );
+        }
+        assert_eq!(alignment.beacons.len(), world.beacons.len());
+    }
+
+    #[test]
+    fn no_overlap() {
+        let world: World = World::generate(&WorldConfig {
+            seed: 9,
+            scanners: 2,
+            ..WorldConfig::default()
+        })
+        .unwrap();
+        let far = Scanner::new(
+            2,
+            world.scanners[1]
+                .beacons
+                .iter()
+                .map(|&b| b + crate::Displacement::new(5000, 0, 0))
+                .collect(),
+        );
+        let config = ApproxConfig {
+            iterations: 50,
+            ..ApproxConfig::default()
+        };
+        assert!(world.scanners[0]
+            .diff_approx(&world.scanners[1], &config)
+            .is_some());
+        // moving every beacon by the same amount doesn't change anything.
+        assert!(world.scanners[0].diff_approx(&far, &config).is_some());
+        // but taking most of them away does.
+        let few = Scanner::new(3, world.scanners[1].beacons[..5].to_vec());
+        assert!(world.scanners[0].diff_approx(&few, &config).is_none());
+    }
+}
@@ -0,0 +1,32 @@
+use std::{env, fs, io};
+
+use anyhow::{bail, Context, Result};
+
+use d19::{diagnostics, AlignConfig};
+
+const USAGE: &str = "usage: diagnose [--dot <file>] < input";
+
+fn main() -> Result<()> {
+    simple_logger::init_with_env().context("couldn't initialize logger")?;
+
+    let mut dot_file = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dot" => dot_file = Some(args.next().context(USAGE)?),
+            _ => bail!(USAGE),
+        }
+    }
+
+    let scanners = d19::parse_input(&mut io::stdin().lines())?;
+    let config = AlignConfig::default();
+    let alignment = d19::align_with(&scanners, &config);
+    let diagnostics = diagnostics::diagnose(&scanners, &alignment, &config);
+    print!("{diagnostics}");
+
+    if let Some(path) = dot_file {
+        fs::write(&path, diagnostics.to_dot()).context(format!("couldn't write {path}"))?;
+    }
+
+    Ok(())
+}
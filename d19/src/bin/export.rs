@@ -0,0 +1,25 @@
+use std::{env, io};
+
+use anyhow::{bail, Context, Result};
+
+use d19::export::WorldMap;
+
+const USAGE: &str = "usage: export <csv | json | ply> < input";
+
+fn main() -> Result<()> {
+    simple_logger::init_with_env().context("couldn't initialize logger")?;
+
+    let format = env::args().nth(1).context(USAGE)?;
+    let scanners = d19::parse_input(&mut io::stdin().lines())?;
+    let map = WorldMap::new(&scanners, &d19::parallel::align_auto(&scanners))?;
+
+    let stdout = io::stdout().lock();
+    match format.as_str() {
+        "csv" => map.write_csv(stdout)?,
+        "json" => map.write_json(stdout)?,
+        "ply" => map.write_ply(stdout)?,
+        _ => bail!(USAGE),
+    }
+
+    Ok(())
+}
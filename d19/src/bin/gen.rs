@@ -0,0 +1,41 @@
+use std::{env, fs, io::Write};
+
+use anyhow::{bail, Context, Result};
+
+use d19::gen::{World, WorldConfig};
+
+const USAGE: &str = "usage: gen [--seed <n>] [--scanners <n>] [--range <n>] [--beacons <n>] [--overlap <n>] [--noise <n>] [--poses <file>]";
+
+fn main() -> Result<()> {
+    simple_logger::init_with_env().context("couldn't initialize logger")?;
+
+    let mut config = WorldConfig::default();
+    let mut poses_file = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().context(USAGE);
+        match arg.as_str() {
+            "--seed" => config.seed = value()?.parse()?,
+            "--scanners" => config.scanners = value()?.parse()?,
+            "--range" => config.range = value()?.parse()?,
+            "--beacons" => config.beacons = value()?.parse()?,
+            "--overlap" => config.overlap = value()?.parse()?,
+            "--noise" => config.noise = value()?.parse()?,
+            "--poses" => poses_file = Some(value()?),
+            _ => bail!(USAGE),
+        }
+    }
+
+    let world: World = World::generate(&config)?;
+    print!("{}", world.to_input());
+
+    // the answers, one scanner per line.
+    if let Some(path) = poses_file {
+        let mut file = fs::File::create(&path).context(format!("couldn't create {path}"))?;
+        for (id, pose) in world.poses.iter().enumerate() {
+            writeln!(file, "{id} {pose}")?;
+        }
+    }
+
+    Ok(())
+}
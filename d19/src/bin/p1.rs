@@ -1,73 +1,22 @@
-use std::io::stdin;
+use std::io;
 
-use anyhow::{Result, Context};
+use anyhow::{Context, Result};
 
-use log::trace;
-
-use d19::{Point, Scanner};
+use log::warn;
 
 fn main() -> Result<()> {
     simple_logger::init_with_env().context("couldn't initialize logger")?;
 
-    // parse
-    let mut scanners = Vec::new();
-    let mut beacons = Vec::new();
-    let mut taxicabs = Vec::new();
-    let lines = stdin().lines();
-    for line in lines {
-        let line = line?;
-        if let Ok(point) = line.parse::<Point>() {
-            let pt_taxicab = point.abs_taxicab();
-            let i = taxicabs.partition_point(|p| p > &pt_taxicab);
-            taxicabs.insert(i, pt_taxicab);
-            beacons.insert(i, point);
-        } else if line.is_empty() {
-            scanners.push(Scanner::new(beacons));
-            beacons = Vec::new();
-            taxicabs.clear();
-        }
-    }
-
-    if !beacons.is_empty() {
-        scanners.push(Scanner::new(beacons));
-    }
-
-    // actual algorithm
-    let mut base = vec![0];
-    loop {
-        let mut any = false;
-        for si in 0..scanners.len() {
-            if !base.contains(&si) {
-                let mut matched = false;
-                for bi in &base {
-                    if let Some((rot, dsp)) = scanners[*bi].diff(&scanners[si]) {
-                        trace!("success! scanner {si} has displacement {dsp} to base");
-                        scanners[si].rotate(&rot);
-                        scanners[si] += dsp;
-                        matched = true;
-                        break;
-                    }
-                }
-                if matched {
-                    base.push(si);
-                    any = true;
-                }
-            }
-        }
-
-        if !any {
-            break;
-        }
-    }
-
-    let mut final_scanner = scanners.remove(0);
-    for bi in base {
-        if bi != 0 {
-            final_scanner.extend(&mut scanners[bi - 1]);
-        }
+    let scanners = d19::parse_input(&mut io::stdin().lines())?;
+    let alignment = d19::parallel::align_auto(&scanners);
+    let unreachable = alignment.unreachable();
+    if !unreachable.is_empty() {
+        warn!(
+            "{} scanners couldn't be placed, so this is probably wrong. try the `diagnose` binary",
+            unreachable.len()
+        );
     }
-
-    println!("{}", final_scanner.beacons.len());
+    println!("{}", alignment.beacons.len());
 
     Ok(())
 }
@@ -95,10 +44,7 @@ mod tests {
     #[test]
     fn rotation() {
         let mut pt = Point::new(1, 2, 3);
-        pt.rotate(&Rotation(
-            Swap::Once(Dim::X, Dim::Z),
-            Reflect::along(Dim::Y),
-        ));
+        pt.rotate(&Rotation::new([[0, 0, 1], [0, -1, 0], [1, 0, 0]]).unwrap());
         assert_eq!(pt, Point::new(3, -2, 1));
     }
 
@@ -113,30 +59,24 @@ mod tests {
     // }
 
     #[test]
-    fn rot_diff() {
+    fn rots_between() {
         let old_pt = Point::new(188, -1408, -168);
         let mut new_pt = old_pt;
-        let rot = Rotation(
-            Swap::Twice(Dim::Z, Dim::Y, Dim::X),
-            Reflect {
-                x: true,
-                y: false,
-                z: true,
-            },
-        );
+        let rot = Rotation::new([[0, 0, -1], [1, 0, 0], [0, -1, 0]]).unwrap();
         new_pt.rotate(&rot);
         assert_eq!(new_pt, Point::new(168, 188, 1408));
-        assert_eq!(old_pt.rot_diff(new_pt), rot);
+        assert_eq!(old_pt.rots_between(new_pt).collect::<Vec<_>>(), vec![rot]);
     }
 
     #[test]
-    fn unswap() {
+    fn unrotate() {
         let old_pt = Point::new(188, -1408, -168);
-        let mut new_pt = old_pt;
-        let swap = Swap::Twice(Dim::Z, Dim::Y, Dim::X);
-        new_pt.swap(&swap);
-        new_pt.unswap(&swap);
-        assert_eq!(old_pt, new_pt);
+        for rot in Rotation::ALL {
+            let mut new_pt = old_pt;
+            new_pt.rotate(&rot);
+            new_pt.unrotate(&rot);
+            assert_eq!(old_pt, new_pt);
+        }
     }
 
     // #[test]
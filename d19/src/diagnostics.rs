@@ -0,0 +1,200 @@
+//! finding out why some scanners couldn't be placed.
+
+use std::fmt::{Display, Write};
+
+use crate::{align::Match, AlignConfig, Alignment, Displacement, Rotation, Scanner};
+
+/// how close an unplaced scanner came to matching another scanner.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PartialOverlap {
+    /// the index of the unplaced scanner.
+    pub scanner: usize,
+    /// the index of the scanner it was compared against.
+    pub other: usize,
+    /// the most beacons which lined up under any one diff. see `Scanner::best_overlap`.
+    pub count: usize,
+    /// the diff which lined them up, from `scanner` into `other`'s frame.
+    pub diff: Option<(Rotation, Displacement)>,
+}
+
+/// what went wrong with an alignment.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Diagnostics {
+    /// the id of each scanner, in input order.
+    pub ids: Vec<usize>,
+    /// the indices of the scanners which couldn't be placed.
+    pub unreachable: Vec<usize>,
+    /// for each unreachable scanner, its best overlap with every other scanner, best first.
+    pub partial: Vec<Vec<PartialOverlap>>,
+    /// the matches which the poses were worked out from.
+    pub matches: Vec<Match>,
+    pub min_overlap: usize,
+}
+
+/// look into `alignment`, which came from aligning `scanners` with `config`. this is slow if many scanners are unreachable, since it compares each of them against every other scanner in full.
+pub fn diagnose(scanners: &[Scanner], alignment: &Alignment, config: &AlignConfig) -> Diagnostics {
+    let unreachable = alignment.unreachable();
+    let partial = unreachable
+        .iter()
+        .map(|&scanner| {
+            let mut overlaps = (0..scanners.len())
+                .filter(|&other| other != scanner)
+                .map(|other| {
+                    let (count, diff) = scanners[other].best_overlap(&scanners[scanner]);
+                    PartialOverlap {
+                        scanner,
+                        other,
+                        count,
+                        diff,
+                    }
+                })
+                .collect::<Vec<_>>();
+            overlaps.sort_by_key(|o| std::cmp::Reverse(o.count));
+            overlaps
+        })
+        .collect();
+
+    Diagnostics {
+        ids: scanners.iter().map(|s| s.id).collect(),
+        unreachable,
+        partial,
+        matches: alignment.matches.clone(),
+        min_overlap: config.min_overlap,
+    }
+}
+
+impl Display for Diagnostics {
+    /// a summary, with the three best partial overlaps for each unreachable scanner.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.unreachable.is_empty() {
+            return writeln!(f, "all {} scanners placed", self.ids.len());
+        }
+        writeln!(
+            f,
+            "{} of {} scanners unreachable",
+            self.unreachable.len(),
+            self.ids.len()
+        )?;
+        for overlaps in &self.partial {
+            let Some(first) = overlaps.first() else {
+                continue;
+            };
+            let best = overlaps
+                .iter()
+                .take(3)
+                .map(|o| {
+                    format!(
+                        "{}/{} with scanner {}",
+                        o.count, self.min_overlap, self.ids[o.other]
+                    )
+                })
+                .collect::<Vec<_>>();
+            writeln!(
+                f,
+                "scanner {}: best overlaps are {}",
+                self.ids[first.scanner],
+                best.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Diagnostics {
+    /// the pose graph as a graphviz digraph. matches are solid edges from the scanner which was already placed. unreachable scanners are red, with a dashed edge to the scanner they came closest to matching, labelled with the overlap.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph poses {\n");
+        for (i, id) in self.ids.iter().enumerate() {
+            if self.unreachable.contains(&i) {
+                writeln!(
+                    out,
+                    "    s{i} [label=\"scanner {id}\", color=red, style=dashed];"
+                )
+                .unwrap();
+            } else {
+                writeln!(out, "    s{i} [label=\"scanner {id}\"];").unwrap();
+            }
+        }
+        for m in &self.matches {
+            writeln!(out, "    s{} -> s{};", m.base, m.other).unwrap();
+        }
+        for best in self.partial.iter().filter_map(|overlaps| overlaps.first()) {
+            writeln!(
+                out,
+                "    s{} -> s{} [style=dashed, color=red, label=\"{}/{}\"];",
+                best.other, best.scanner, best.count, self.min_overlap
+            )
+            .unwrap();
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{align, Point};
+
+    #[test]
+    fn all_placed() {
+        let scanners = crate::tests::example();
+        let alignment = align(&scanners);
+        let diagnostics = diagnose(&scanners, &alignment, &AlignConfig::default());
+        assert!(diagnostics.unreachable.is_empty());
+        assert_eq!(diagnostics.to_string(), "all 5 scanners placed\n");
+        let dot = diagnostics.to_dot();
+        assert!(dot.starts_with("digraph poses {\n    s0 [label=\"scanner 0\"];\n"));
+        assert!(dot.contains("    s0 -> s1;\n"));
+        assert!(dot.contains("    s1 -> s4;\n"));
+        assert!(!dot.contains("dashed"));
+    }
+
+    #[test]
+    fn unreachable() {
+        let mut scanners = crate::tests::example();
+        // scanner 1 with all but 8 of the beacons it shares with scanner 0 taken away, and no longer overlapping anything else.
+        let alignment = align(&scanners);
+        let pose = alignment.poses[1].unwrap();
+        let shared = scanners[0]
+            .beacons
+            .iter()
+            .filter(|&&b| scanners[1].beacons.iter().any(|&o| pose.apply(o) == b))
+            .copied()
+            .collect::<Vec<_>>();
+        assert_eq!(shared.len(), 12);
+        let undo = |b: Point| {
+            pose.rotation
+                .inverse()
+                .apply(Point::default() + (b - pose.position))
+        };
+        scanners[1] = Scanner::new(7, shared[..8].iter().map(|&b| undo(b)).collect());
+
+        let alignment = align(&scanners);
+        // everything else was only reachable through 1.
+        assert_eq!(alignment.unreachable(), vec![1, 2, 3, 4]);
+        assert_eq!(alignment.beacons.len(), 25);
+
+        let diagnostics = diagnose(&scanners, &alignment, &AlignConfig::default());
+        assert_eq!(diagnostics.unreachable, vec![1, 2, 3, 4]);
+        let best = diagnostics.partial[0][0];
+        assert_eq!((best.scanner, best.other, best.count), (1, 0, 8));
+        let (rot, dsp) = best.diff.unwrap();
+        assert_eq!(
+            (rot, Point::default() + dsp),
+            (pose.rotation, pose.position)
+        );
+        // 2 and 4 still overlap each other, just not anything placed.
+        assert_eq!(diagnostics.partial[1][0].other, 4);
+        assert!(diagnostics.partial[1][0].count >= 12);
+
+        let report = diagnostics.to_string();
+        assert!(report.starts_with(
+            "4 of 5 scanners unreachable\nscanner 7: best overlaps are 8/12 with scanner 0, "
+        ));
+        let dot = diagnostics.to_dot();
+        assert!(dot.contains("    s1 [label=\"scanner 7\", color=red, style=dashed];\n"));
+        assert!(dot.contains("    s0 -> s1 [style=dashed, color=red, label=\"8/12\"];\n"));
+        assert!(!dot.contains("    s0 -> s1;\n"));
+    }
+}
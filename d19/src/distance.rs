@@ -0,0 +1,243 @@
+//! distances between scanners, or any other points: whole matrices of them, and the nearest and farthest pairs.
+
+use crate::Point;
+
+/// a way of measuring the distance between two points.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Metric {
+    /// the sum of the differences along each axis, as in part 2.
+    Taxicab,
+    /// the straight line distance. see `Metric::dist`.
+    Euclidean,
+    /// the greatest difference along any one axis. a scanner sees everything within its range by this metric.
+    Chebyshev,
+}
+
+impl Metric {
+    pub const ALL: [Self; 3] = [Self::Taxicab, Self::Euclidean, Self::Chebyshev];
+
+    /// the distance between `a` and `b`. for `Euclidean`, this is the square of the distance, which keeps it exact and doesn't change which of two distances is bigger.
+    pub fn dist<const N: usize>(self, a: &Point<N>, b: &Point<N>) -> u64 {
+        match self {
+            Self::Taxicab => a.dist_taxicab(b),
+            Self::Euclidean => a.dist_squclid(b),
+            Self::Chebyshev => a.dist_chebyshev(b),
+        }
+    }
+}
+
+/// two points from a list, by index with `a < b`, and the distance between them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Pair {
+    pub a: usize,
+    pub b: usize,
+    pub distance: u64,
+}
+
+impl Pair {
+    fn new(a: usize, b: usize, distance: u64) -> Self {
+        Self {
+            a: a.min(b),
+            b: a.max(b),
+            distance,
+        }
+    }
+}
+
+/// the distance between every pair of `points`.
+pub fn matrix<const N: usize>(points: &[Point<N>], metric: Metric) -> Vec<Vec<u64>> {
+    points
+        .iter()
+        .map(|a| points.iter().map(|b| metric.dist(a, b)).collect())
+        .collect()
+}
+
+/// the pair of `points` which are closest together, the first in order if there's a tie. `None` if there are fewer than two points. this checks every pair.
+pub fn nearest_pair<const N: usize>(points: &[Point<N>], metric: Metric) -> Option<Pair> {
+    let mut best: Option<Pair> = None;
+    for (i, a) in points.iter().enumerate() {
+        for (j, b) in points.iter().enumerate().skip(i + 1) {
+            let distance = metric.dist(a, b);
+            if best.is_none_or(|best| distance < best.distance) {
+                best = Some(Pair::new(i, j, distance));
+            }
+        }
+    }
+    best
+}
+
+/// a pair of `points` which are as far apart as any, or `None` if there are fewer than two points. for `Taxicab` and `Chebyshev` this takes linear time, but `Euclidean` checks every pair.
+pub fn farthest_pair<const N: usize>(points: &[Point<N>], metric: Metric) -> Option<Pair> {
+    if points.len() < 2 {
+        return None;
+    }
+    let best = match metric {
+        // the taxicab distance between a and b is the biggest of `s.a - s.b`, over every vector `s` of 1s and -1s. so for each `s`, the two points farthest apart along it are the ones with the biggest and smallest dot product with it. `s` and `-s` find the same pair, so the first sign is always 1.
+        Metric::Taxicab => (0..1_u64 << N.saturating_sub(1))
+            .map(|signs| {
+                extremes(points, |pt| {
+                    pt.coords()
+                        .iter()
+                        .enumerate()
+                        .map(|(axis, &n)| {
+                            let n = i64::from(n);
+                            if axis > 0 && signs >> (axis - 1) & 1 == 1 {
+                                -n
+                            } else {
+                                n
+                            }
+                        })
+                        .sum()
+                })
+            })
+            .max_by_key(|pair| pair.distance),
+        // and the chebyshev distance is the biggest difference along any axis.
+        Metric::Chebyshev => (0..N)
+            .map(|axis| extremes(points, |pt| i64::from(pt.coords()[axis])))
+            .max_by_key(|pair| pair.distance),
+        Metric::Euclidean => {
+            let mut best = Pair::new(0, 1, metric.dist(&points[0], &points[1]));
+            for (i, a) in points.iter().enumerate() {
+                for (j, b) in points.iter().enumerate().skip(i + 1) {
+                    let distance = metric.dist(a, b);
+                    if distance > best.distance {
+                        best = Pair::new(i, j, distance);
+                    }
+                }
+            }
+            Some(best)
+        }
+    };
+
+    // with every point in the same place, the extremes can both be the same point.
+    best.map(|pair| {
+        if pair.a == pair.b {
+            Pair::new(0, 1, 0)
+        } else {
+            pair
+        }
+    })
+}
+
+/// the points with the smallest and biggest `key`, and the difference between them.
+fn extremes<const N: usize>(points: &[Point<N>], key: impl Fn(&Point<N>) -> i64) -> Pair {
+    let keys = points.iter().map(key).collect::<Vec<_>>();
+    let (mut min, mut max) = (0, 0);
+    for (i, &k) in keys.iter().enumerate() {
+        if k < keys[min] {
+            min = i;
+        }
+        if k > keys[max] {
+            max = i;
+        }
+    }
+    Pair::new(min, max, keys[max].abs_diff(keys[min]))
+}
+
+#[cfg(test)]
+mod tests {
+    use common::rng::Rng;
+
+    use super::*;
+    use crate::Coord;
+
+    /// the farthest pair, by checking every pair.
+    fn brute_farthest<const N: usize>(points: &[Point<N>], metric: Metric) -> u64 {
+        matrix(points, metric)
+            .iter()
+            .flatten()
+            .copied()
+            .max()
+            .unwrap()
+    }
+
+    fn random_points<const N: usize>(rng: &mut Rng, count: usize, spread: i64) -> Vec<Point<N>> {
+        (0..count)
+            .map(|_| {
+                let mut coords = [0; N];
+                for n in &mut coords {
+                    *n = rng.range(-spread..spread + 1) as Coord;
+                }
+                Point::from(coords)
+            })
+            .collect()
+    }
+
+    fn check<const N: usize>(rng: &mut Rng) {
+        for count in [2, 3, 10, 50] {
+            // big enough to need most of a u64 for squared distances in 4d.
+            for spread in [0, 3, 1000, 1 << 29] {
+                let points = random_points::<N>(rng, count, spread);
+                for metric in Metric::ALL {
+                    let far = farthest_pair(&points, metric).unwrap();
+                    assert!(far.a < far.b);
+                    assert_eq!(far.distance, brute_farthest(&points, metric));
+                    assert_eq!(far.distance, metric.dist(&points[far.a], &points[far.b]));
+
+                    let near = nearest_pair(&points, metric).unwrap();
+                    assert!(near.a < near.b);
+                    assert_eq!(near.distance, metric.dist(&points[near.a], &points[near.b]));
+                    let m = matrix(&points, metric);
+                    for (i, row) in m.iter().enumerate() {
+                        assert_eq!(row[i], 0);
+                        for (j, &d) in row.iter().enumerate().skip(i + 1) {
+                            assert_eq!(d, m[j][i]);
+                            assert!(d >= near.distance);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn metrics() {
+        let (a, b) = (Point::new(1, -2, 3), Point::new(-3, 5, 3));
+        assert_eq!(Metric::Taxicab.dist(&a, &b), 11);
+        assert_eq!(Metric::Euclidean.dist(&a, &b), 16 + 49);
+        assert_eq!(Metric::Chebyshev.dist(&a, &b), 7);
+        for metric in Metric::ALL {
+            assert_eq!(metric.dist(&a, &a), 0);
+            assert_eq!(metric.dist(&a, &b), metric.dist(&b, &a));
+        }
+    }
+
+    #[test]
+    fn pairs() {
+        let mut rng = Rng::new(44);
+        check::<1>(&mut rng);
+        check::<2>(&mut rng);
+        check::<3>(&mut rng);
+        check::<4>(&mut rng);
+
+        for metric in Metric::ALL {
+            assert_eq!(farthest_pair::<3>(&[], metric), None);
+            assert_eq!(nearest_pair(&[Point::new(1, 2, 3)], metric), None);
+        }
+        let points = [
+            Point::new(0, 0, 0),
+            Point::new(5, 5, 0),
+            Point::new(1, 1, 1),
+        ];
+        assert_eq!(
+            nearest_pair(&points, Metric::Taxicab),
+            Some(Pair {
+                a: 0,
+                b: 2,
+                distance: 3
+            })
+        );
+        assert_eq!(
+            farthest_pair(&points, Metric::Taxicab),
+            Some(Pair {
+                a: 0,
+                b: 1,
+                distance: 10
+            })
+        );
+        assert_eq!(
+            farthest_pair(&points, Metric::Chebyshev).unwrap().distance,
+            5
+        );
+    }
+}
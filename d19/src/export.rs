@@ -0,0 +1,304 @@
+//! writing out the merged world after alignment, for looking at in other tools.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    io::{self, BufRead, Write},
+};
+
+use anyhow::{bail, Context, Result};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Alignment, Point, Pose, Scanner};
+
+/// a scanner, and where it turned out to be.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MappedScanner {
+    /// the scanner's id from the input.
+    pub id: usize,
+    /// `None` if it couldn't be placed.
+    pub pose: Option<Pose>,
+}
+
+/// a beacon, relative to the first scanner in the input, and the ids of the scanners which saw it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MappedBeacon {
+    pub position: Point,
+    pub seen_by: Vec<usize>,
+}
+
+/// everything that alignment found out about the world.
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct WorldMap {
+    pub scanners: Vec<MappedScanner>,
+    pub beacons: Vec<MappedBeacon>,
+}
+
+const CSV_HEADER: &str = "kind,id,x,y,z,rotation,seen_by";
+
+impl WorldMap {
+    /// the map of `scanners` as placed by `alignment`. beacons are in the same order as `alignment.beacons`, and each one's `seen_by` is sorted. fails if `alignment` isn't an alignment of `scanners`.
+    pub fn new(scanners: &[Scanner], alignment: &Alignment) -> Result<Self> {
+        if scanners.len() != alignment.poses.len() {
+            bail!(
+                "the alignment has poses for {} scanners, not {}",
+                alignment.poses.len(),
+                scanners.len()
+            );
+        }
+        let index = alignment
+            .beacons
+            .iter()
+            .enumerate()
+            .map(|(i, &b)| (b, i))
+            .collect::<HashMap<_, _>>();
+        let mut seen_by = vec![BTreeSet::new(); alignment.beacons.len()];
+        for (scanner, pose) in scanners.iter().zip(&alignment.poses) {
+            if let Some(pose) = pose {
+                for &beacon in &scanner.beacons {
+                    // a pose which moves a beacon off the edge of the world doesn't belong to these scanners either.
+                    let moved = pose
+                        .rotation
+                        .apply(beacon)
+                        .checked_add(pose.position - Point::ORIGIN);
+                    let &i = moved.and_then(|b| index.get(&b)).with_context(|| {
+                        format!(
+                            "scanner {} sees a beacon at {beacon}, which isn't in the alignment",
+                            scanner.id
+                        )
+                    })?;
+                    seen_by[i].insert(scanner.id);
+                }
+            }
+        }
+
+        Ok(Self {
+            scanners: scanners
+                .iter()
+                .zip(&alignment.poses)
+                .map(|(scanner, &pose)| MappedScanner {
+                    id: scanner.id,
+                    pose,
+                })
+                .collect(),
+            beacons: alignment
+                .beacons
+                .iter()
+                .zip(seen_by)
+                .map(|(&position, seen_by)| MappedBeacon {
+                    position,
+                    seen_by: seen_by.into_iter().collect(),
+                })
+                .collect(),
+        })
+    }
+
+    /// one row per scanner, then one per beacon. scanners have an empty `seen_by`, beacons have an empty `id` and `rotation`, and unplaced scanners have nothing but a `kind` and `id`. `seen_by` is a space separated list of ids.
+    pub fn write_csv<W: Write>(&self, mut w: W) -> io::Result<()> {
+        writeln!(w, "{CSV_HEADER}")?;
+        for scanner in &self.scanners {
+            match scanner.pose {
+                Some(Pose { rotation, position }) => {
+                    writeln!(w, "scanner,{},{position},{rotation},", scanner.id)?
+                }
+                None => writeln!(w, "scanner,{},,,,,", scanner.id)?,
+            }
+        }
+        for beacon in &self.beacons {
+            let seen_by = beacon
+                .seen_by
+                .iter()
+                .map(usize::to_string)
+                .collect::<Vec<_>>()
+                .join(" ");
+            writeln!(w, "beacon,,{},,{seen_by}", beacon.position)?;
+        }
+        Ok(())
+    }
+
+    pub fn read_csv<R: BufRead>(r: R) -> Result<Self> {
+        let mut res = Self::default();
+        let mut lines = r.lines();
+        let header = lines.next().context("line 1: unexpected end of input")??;
+        if header.trim_end() != CSV_HEADER {
+            bail!("line 1: expected `{CSV_HEADER}`");
+        }
+
+        for (i, line) in lines.enumerate() {
+            let n = i + 2;
+            let line = line?;
+            let line = line.trim_end();
+            if line.is_empty() {
+                continue;
+            }
+            let fields = line.split(',').collect::<Vec<_>>();
+            let [kind, id, x, y, z, rotation, seen_by] = fields[..] else {
+                bail!("line {n}: expected 7 fields, found {}", fields.len());
+            };
+            let position = || -> Result<Point> { format!("{x},{y},{z}").parse() };
+            match kind {
+                "scanner" => {
+                    let id = id.parse().with_context(|| format!("line {n}: bad id"))?;
+                    let pose = if rotation.is_empty() {
+                        None
+                    } else {
+                        Some(Pose {
+                            rotation: rotation.parse().with_context(|| format!("line {n}"))?,
+                            position: position().with_context(|| format!("line {n}"))?,
+                        })
+                    };
+                    res.scanners.push(MappedScanner { id, pose });
+                }
+                "beacon" => res.beacons.push(MappedBeacon {
+                    position: position().with_context(|| format!("line {n}"))?,
+                    seen_by: seen_by
+                        .split_whitespace()
+                        .map(str::parse)
+                        .collect::<Result<_, _>>()
+                        .with_context(|| format!("line {n}: bad scanner id"))?,
+                }),
+                _ => bail!("line {n}: expected `scanner` or `beacon`, found `{kind}`"),
+            }
+        }
+
+        Ok(res)
+    }
+
+    pub fn write_json<W: Write>(&self, w: W) -> Result<()> {
+        serde_json::to_writer_pretty(w, self)?;
+        Ok(())
+    }
+
+    pub fn read_json<R: BufRead>(r: R) -> Result<Self> {
+        Ok(serde_json::from_reader(r)?)
+    }
+
+    /// an ascii PLY point cloud, with a vertex for every beacon in white and every placed scanner in red. each vertex also has a `scanner` property, which is the scanner's id, or -1 for beacons, and `observers`, the number of scanners which saw it.
+    pub fn write_ply<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let placed = self
+            .scanners
+            .iter()
+            .filter_map(|s| s.pose.map(|pose| (s.id, pose.position)))
+            .collect::<Vec<_>>();
+        writeln!(w, "ply")?;
+        writeln!(w, "format ascii 1.0")?;
+        writeln!(
+            w,
+            "comment beacons and scanners, relative to the first scanner"
+        )?;
+        writeln!(w, "element vertex {}", self.beacons.len() + placed.len())?;
+        for prop in ["int x", "int y", "int z"] {
+            writeln!(w, "property {prop}")?;
+        }
+        for prop in ["uchar red", "uchar green", "uchar blue"] {
+            writeln!(w, "property {prop}")?;
+        }
+        writeln!(w, "property int scanner")?;
+        writeln!(w, "property int observers")?;
+        writeln!(w, "end_header")?;
+
+        for beacon in &self.beacons {
+            let [x, y, z] = beacon.position.coords();
+            writeln!(w, "{x} {y} {z} 255 255 255 -1 {}", beacon.seen_by.len())?;
+        }
+        for (id, position) in placed {
+            let [x, y, z] = position.coords();
+            writeln!(w, "{x} {y} {z} 255 0 0 {id} 0")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::align;
+
+    fn example() -> WorldMap {
+        let scanners = crate::tests::example();
+        WorldMap::new(&scanners, &align(&scanners)).unwrap()
+    }
+
+    #[test]
+    fn new() {
+        let map = example();
+        assert_eq!(map.scanners.len(), 5);
+        assert_eq!(map.beacons.len(), 79);
+        assert_eq!(
+            map.scanners[1].pose.unwrap().position,
+            Point::new(68, -1246, -43)
+        );
+        // every beacon was seen by someone, and the overlaps add up: scanners 0 and 1 share 12.
+        assert!(map.beacons.iter().all(|b| !b.seen_by.is_empty()));
+        let both = map
+            .beacons
+            .iter()
+            .filter(|b| b.seen_by.contains(&0) && b.seen_by.contains(&1))
+            .count();
+        assert_eq!(both, 12);
+        let sightings = map.beacons.iter().map(|b| b.seen_by.len()).sum::<usize>();
+        let reports = crate::tests::example()
+            .iter()
+            .map(|s| s.beacons.len())
+            .sum::<usize>();
+        assert_eq!(sightings, reports);
+
+        // an alignment of some other scanners doesn't fit.
+        let scanners = crate::tests::example();
+        let mut alignment = align(&scanners);
+        assert!(WorldMap::new(&scanners[..4], &alignment).is_err());
+        alignment.beacons.remove(0);
+        let err = WorldMap::new(&scanners, &alignment).unwrap_err();
+        assert!(
+            err.to_string().contains("which isn't in the alignment"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let mut map = example();
+        map.scanners.push(MappedScanner { id: 9, pose: None });
+        let mut out = Vec::new();
+        map.write_csv(&mut out).unwrap();
+        let text = String::from_utf8(out.clone()).unwrap();
+        assert!(text.starts_with("kind,id,x,y,z,rotation,seen_by\nscanner,0,0,0,0,+x+y+z,\n"));
+        assert!(text.contains("\nscanner,9,,,,,\n"));
+        assert_eq!(WorldMap::read_csv(&out[..]).unwrap(), map);
+
+        assert!(WorldMap::read_csv("kind,id\n".as_bytes()).is_err());
+        let err = WorldMap::read_csv(format!("{CSV_HEADER}\nbeacon,,1,2,3,,0 x\n").as_bytes())
+            .unwrap_err();
+        assert!(err.to_string().starts_with("line 2"));
+        assert!(
+            WorldMap::read_csv(format!("{CSV_HEADER}\nscanner,1,1,2,3,+x+x+z,\n").as_bytes())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let mut map = example();
+        map.scanners.push(MappedScanner { id: 9, pose: None });
+        let mut out = Vec::new();
+        map.write_json(&mut out).unwrap();
+        assert_eq!(WorldMap::read_json(&out[..]).unwrap(), map);
+
+        // rotations have to be real rotations.
+        let bad = r#"{"scanners": [{"id": 0, "pose": {"rotation": [[1, 0, 0], [0, 1, 0], [0, 0, -1]], "position": {"x": 0, "y": 0, "z": 0}}}], "beacons": []}"#;
+        assert!(WorldMap::read_json(bad.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn ply() {
+        let map = example();
+        let mut out = Vec::new();
+        map.write_ply(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let (header, body) = text.split_once("end_header\n").unwrap();
+        assert!(header.starts_with("ply\nformat ascii 1.0\n"));
+        assert!(header.contains("element vertex 84\n"));
+        assert_eq!(body.lines().count(), 84);
+        assert_eq!(body.lines().last(), Some("-20 -1133 1061 255 0 0 4 0"));
+    }
+}
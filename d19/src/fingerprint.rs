@@ -0,0 +1,105 @@
+//! a cheap test for whether two scanners could possibly overlap, to save running `Scanner::diff` on pairs which can't.
+
+use crate::{Scanner, MIN_OVERLAP};
+
+/// the number of distances that two scanners which overlap by `min_overlap` beacons must have in common: one for each pair of shared beacons.
+pub const fn min_shared_dists(min_overlap: usize) -> usize {
+    min_overlap * min_overlap.saturating_sub(1) / 2
+}
+
+/// `min_shared_dists` for the default `MIN_OVERLAP`.
+pub const MIN_SHARED_DISTS: usize = min_shared_dists(MIN_OVERLAP);
+
+/// the squared distances between every pair of a scanner's beacons, sorted. these don't change however the scanner is rotated or moved.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct Fingerprint(Vec<u64>);
+
+impl Fingerprint {
+    pub fn new<const N: usize>(scanner: &Scanner<N>) -> Self {
+        let beacons = &scanner.beacons;
+        let mut dists = Vec::with_capacity(beacons.len() * beacons.len().saturating_sub(1) / 2);
+        for (i, a) in beacons.iter().enumerate() {
+            for b in &beacons[i + 1..] {
+                dists.push(a.dist_squclid(b));
+            }
+        }
+        dists.sort_unstable();
+        Self(dists)
+    }
+
+    pub fn dists(&self) -> &[u64] {
+        &self.0
+    }
+
+    /// the size of the multiset intersection of `self` and `other`, i.e. how many distances they have in common, counting repeats.
+    pub fn shared(&self, other: &Self) -> usize {
+        let (mut i, mut j) = (0, 0);
+        let mut count = 0;
+        while let (Some(a), Some(b)) = (self.0.get(i), other.0.get(j)) {
+            match a.cmp(b) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => {
+                    count += 1;
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        count
+    }
+
+    /// whether the two scanners have enough distances in common that they might overlap by `min_overlap` beacons. if this is `false`, they definitely don't.
+    pub fn plausible(&self, other: &Self, min_overlap: usize) -> bool {
+        self.shared(other) >= min_shared_dists(min_overlap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{align, Point, Rotation};
+
+    #[test]
+    fn shared() {
+        let fp = |dists: &[u64]| Fingerprint(dists.to_vec());
+        assert_eq!(fp(&[1, 2, 2, 2, 5]).shared(&fp(&[2, 2, 5, 5, 7])), 3);
+        assert_eq!(fp(&[]).shared(&fp(&[1])), 0);
+        assert_eq!(MIN_SHARED_DISTS, 66);
+        assert_eq!(min_shared_dists(3), 3);
+        assert_eq!(min_shared_dists(0), 0);
+
+        let scanner = Scanner::new(
+            0,
+            vec![
+                Point::new(0, 0, 0),
+                Point::new(3, 4, 0),
+                Point::new(0, 0, 1),
+            ],
+        );
+        assert_eq!(Fingerprint::new(&scanner).dists(), [1, 25, 26]);
+        let moved = Scanner::new(
+            1,
+            scanner
+                .beacons
+                .iter()
+                .map(|&b| Rotation::ALL[17].apply(b) + (Point::new(9, 9, 9) - Point::default()))
+                .collect(),
+        );
+        assert_eq!(Fingerprint::new(&scanner), Fingerprint::new(&moved));
+    }
+
+    #[test]
+    fn example() {
+        let scanners = crate::tests::example();
+        let fps = scanners.iter().map(Fingerprint::new).collect::<Vec<_>>();
+        let alignment = align(&scanners);
+        // every pair which was actually matched must get through the filter.
+        for m in &alignment.matches {
+            assert!(fps[m.base].plausible(&fps[m.other], MIN_OVERLAP));
+        }
+        // and 0 and 2 don't overlap, which the fingerprints can already tell.
+        assert!(!fps[0].plausible(&fps[2], MIN_OVERLAP));
+        assert!(scanners[0].diff(&scanners[2]).is_none());
+    }
+}
@@ -0,0 +1,403 @@
+//! random scanner worlds with known answers, for testing `align` on more than the example.
+
+use anyhow::{bail, ensure, Context, Result};
+use common::rng::Rng;
+
+use crate::{map::BeaconMap, Coord, Displacement, Point, Pose, Rotation, Scanner};
+
+/// what kind of world to generate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WorldConfig {
+    pub seed: u64,
+    pub scanners: usize,
+    /// how far a scanner can see along each axis, as in the puzzle.
+    pub range: Coord,
+    /// how many beacons each new scanner should be able to see, at least.
+    pub beacons: usize,
+    /// how many beacons each new scanner is guaranteed to share with some earlier one.
+    pub overlap: usize,
+    /// how far out each coordinate of each sighting may be, for testing the approximate matcher. with any noise, different scanners will disagree slightly about where the same beacon is.
+    pub noise: Coord,
+}
+
+impl Default for WorldConfig {
+    /// a world shaped like the real puzzle input.
+    fn default() -> Self {
+        Self {
+            seed: 19,
+            scanners: 30,
+            range: 1000,
+            beacons: 26,
+            overlap: 12,
+            noise: 0,
+        }
+    }
+}
+
+impl WorldConfig {
+    /// check that a world in `N` dimensions can be generated from this: that there's room in each scanner's cube for the beacons and the overlap, and that nothing can end up outside a `Coord`.
+    pub fn validate<const N: usize>(&self) -> Result<()> {
+        ensure!(
+            self.range >= 0,
+            "the range can't be negative, but it's {}",
+            self.range
+        );
+        ensure!(
+            self.noise >= 0,
+            "the noise can't be negative, but it's {}",
+            self.noise
+        );
+
+        // the number of points in a cube `side` wide, if it fits in a u64.
+        let room = |side: i64| u64::try_from(side).ok()?.checked_pow(N as u32);
+        let range = i64::from(self.range);
+        if room(2 * range + 1).is_some_and(|room| room < self.beacons as u64) {
+            bail!(
+                "a scanner with range {range} can see at most {} beacons, not {}",
+                room(2 * range + 1).unwrap(),
+                self.beacons
+            );
+        }
+        // a scanner is placed within range of an earlier one, so they always share a cube at least `range + 1` wide.
+        if room(range + 1).is_some_and(|room| room < self.overlap as u64) {
+            bail!(
+                "two scanners with range {range} might only share {} beacons, not {}",
+                room(range + 1).unwrap(),
+                self.overlap
+            );
+        }
+        // every scanner is within range of the one before it in the chain, and can see `range` past itself.
+        let reach = i64::try_from(self.scanners)
+            .ok()
+            .and_then(|scanners| scanners.checked_mul(range))
+            .and_then(|reach| reach.checked_add(i64::from(self.noise)));
+        if reach.is_none_or(|reach| reach > i64::from(Coord::MAX)) {
+            bail!(
+                "{} scanners with range {range} and noise {} could end up too far out for a `Coord`",
+                self.scanners,
+                self.noise
+            );
+        }
+        Ok(())
+    }
+}
+
+/// a generated world in `N` dimensions: what each scanner sees, and where everything really is.
+#[derive(Clone, Debug)]
+pub struct World<const N: usize = 3> {
+    /// each scanner's report, in its own reference frame, with beacons in no particular order, and with noise if the config asked for any.
+    pub scanners: Vec<Scanner<N>>,
+    /// the true pose of each scanner. the first, with id 0, is always at `Pose::ORIGIN`.
+    pub poses: Vec<Pose<N>>,
+    /// every beacon in the world, relative to the first scanner. each is seen by at least one scanner.
+    pub beacons: Vec<Point<N>>,
+}
+
+fn random_point<const N: usize>(rng: &mut Rng, min: Point<N>, max: Point<N>) -> Point<N> {
+    let (min, max) = (min.coords(), max.coords());
+    let mut coords = [0; N];
+    for (i, coord) in coords.iter_mut().enumerate() {
+        *coord = rng.range(i64::from(min[i])..i64::from(max[i]) + 1) as Coord;
+    }
+    Point::from(coords)
+}
+
+impl<const N: usize> World<N> {
+    /// generate a world, or fail if `config` doesn't pass `WorldConfig::validate`.
+    pub fn generate(config: &WorldConfig) -> Result<Self> {
+        config.validate::<N>()?;
+        let mut rng = Rng::new(config.seed);
+        let rotations = Rotation::<N>::all().collect::<Vec<_>>();
+        let range = config.range;
+        let cube = |pos: Point<N>| {
+            (
+                Point::from(pos.coords().map(|n| n - range)),
+                Point::from(pos.coords().map(|n| n + range)),
+            )
+        };
+        let sees = |pos: Point<N>, beacon: Point<N>| {
+            let (min, max) = cube(pos);
+            (0..N).all(|i| (min.coords()[i]..=max.coords()[i]).contains(&beacon.coords()[i]))
+        };
+
+        let mut poses: Vec<Pose<N>> = Vec::with_capacity(config.scanners);
+        // every beacon placed so far, for counting the ones near a scanner without looking at them all.
+        let mut map = BeaconMap::new();
+
+        for i in 0..config.scanners {
+            let pose = if i == 0 {
+                Pose::ORIGIN
+            } else {
+                // close enough to some earlier scanner that their cubes overlap by at least half on every axis.
+                let parent = poses[rng.index(i)].position;
+                let (near_min, near_max) = cube(parent);
+                let position = random_point(&mut rng, near_min, near_max);
+
+                let (min, max) = cube(position);
+                let (parent_min, parent_max) = cube(parent);
+                let mut shared_min = min.coords();
+                let mut shared_max = max.coords();
+                for i in 0..N {
+                    shared_min[i] = shared_min[i].max(parent_min.coords()[i]);
+                    shared_max[i] = shared_max[i].min(parent_max.coords()[i]);
+                }
+                let (shared_min, shared_max) = (Point::from(shared_min), Point::from(shared_max));
+                let mut shared = map
+                    .within(position, range.unsigned_abs())
+                    .into_iter()
+                    .filter(|&b| sees(parent, b))
+                    .count();
+                while shared < config.overlap {
+                    if map.observe(random_point(&mut rng, shared_min, shared_max)) {
+                        shared += 1;
+                    }
+                }
+
+                Pose {
+                    rotation: rotations[rng.index(rotations.len())],
+                    position,
+                }
+            };
+
+            let (min, max) = cube(pose.position);
+            let mut visible = map.within(pose.position, range.unsigned_abs()).len();
+            while visible < config.beacons {
+                if map.observe(random_point(&mut rng, min, max)) {
+                    visible += 1;
+                }
+            }
+            poses.push(pose);
+        }
+
+        // only now that every beacon is placed do we know what each scanner can see.
+        let mut scanners = Vec::with_capacity(poses.len());
+        for (id, pose) in poses.iter().enumerate() {
+            let undo = pose.rotation.inverse();
+            let mut seen = map
+                .within(pose.position, range.unsigned_abs())
+                .into_iter()
+                .map(|b| undo.apply(Point::ORIGIN + (b - pose.position)))
+                .collect::<Vec<_>>();
+            if config.noise > 0 {
+                let noise = i64::from(config.noise);
+                for b in &mut seen {
+                    let jitter =
+                        Displacement::from([(); N].map(|_| rng.range(-noise..noise + 1) as Coord));
+                    *b = b
+                        .checked_add(jitter)
+                        .with_context(|| format!("noise pushed {b} out of range"))?;
+                }
+            }
+            rng.shuffle(&mut seen);
+            scanners.push(Scanner::new(id, seen));
+        }
+
+        Ok(Self {
+            scanners,
+            poses,
+            beacons: map.into_beacons(),
+        })
+    }
+
+    /// the scanners' reports, in the same format as the puzzle input.
+    pub fn to_input(&self) -> String {
+        self.scanners
+            .iter()
+            .map(Scanner::to_string)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, io::BufRead};
+
+    use super::*;
+    use crate::{align_with, parse_input_dims, AlignConfig};
+
+    /// generate a world, write it out, read it back in, and check that `align` gets everything right.
+    fn round_trip<const N: usize>(config: &WorldConfig) {
+        let world = World::<N>::generate(config).unwrap();
+        assert_eq!(world.poses[0], Pose::ORIGIN);
+        assert!(world
+            .scanners
+            .iter()
+            .all(|s| s.beacons.len() >= config.beacons));
+
+        let input = world.to_input();
+        let scanners = parse_input_dims::<N, _>(&mut input.as_bytes().lines()).unwrap();
+        assert_eq!(scanners, world.scanners);
+
+        let alignment = align_with(
+            &scanners,
+            &AlignConfig {
+                min_overlap: config.overlap,
+                range: Some(config.range),
+            },
+        );
+        for (id, (found, expected)) in alignment.poses.iter().zip(&world.poses).enumerate() {
+            assert_eq!(found.as_ref(), Some(expected), "scanner {id} in {config:?}");
+        }
+        assert_eq!(alignment.beacons.len(), world.beacons.len(), "{config:?}");
+        let expected = world.beacons.iter().collect::<HashSet<_>>();
+        assert!(alignment.beacons.iter().all(|b| expected.contains(b)));
+    }
+
+    #[test]
+    fn reproducible() {
+        let config = WorldConfig::default();
+        assert_eq!(
+            World::<3>::generate(&config).unwrap().to_input(),
+            World::<3>::generate(&config).unwrap().to_input()
+        );
+        let other = WorldConfig { seed: 20, ..config };
+        assert_ne!(
+            World::<3>::generate(&config).unwrap().to_input(),
+            World::<3>::generate(&other).unwrap().to_input()
+        );
+    }
+
+    #[test]
+    fn impossible() {
+        let err = |config: WorldConfig| World::<3>::generate(&config).unwrap_err().to_string();
+        let config = WorldConfig::default();
+        assert_eq!(
+            err(WorldConfig {
+                range: 0,
+                beacons: 2,
+                ..config
+            }),
+            "a scanner with range 0 can see at most 1 beacons, not 2"
+        );
+        assert_eq!(
+            err(WorldConfig {
+                range: 1,
+                overlap: 9,
+                ..config
+            }),
+            "two scanners with range 1 might only share 8 beacons, not 9"
+        );
+        assert!(err(WorldConfig {
+            range: -1,
+            ..config
+        })
+        .contains("negative"));
+        assert!(err(WorldConfig {
+            noise: -1,
+            ..config
+        })
+        .contains("negative"));
+        assert!(err(WorldConfig {
+            range: Coord::MAX / 2,
+            ..config
+        })
+        .contains("too far out"));
+
+        // just enough room.
+        let world = World::<3>::generate(&WorldConfig {
+            range: 1,
+            beacons: 27,
+            overlap: 8,
+            scanners: 5,
+            ..config
+        })
+        .unwrap();
+        assert!(world.scanners.iter().all(|s| s.beacons.len() == 27));
+        assert!(World::<1>::generate(&WorldConfig {
+            range: 4,
+            beacons: 9,
+            overlap: 5,
+            ..config
+        })
+        .is_ok());
+    }
+
+    #[test]
+    fn every_rotation() {
+        // enough scanners that all 24 orientations get used.
+        let config = WorldConfig {
+            scanners: 120,
+            ..WorldConfig::default()
+        };
+        let world: World = World::generate(&config).unwrap();
+        let rotations = world
+            .poses
+            .iter()
+            .map(|p| p.rotation)
+            .collect::<HashSet<_>>();
+        assert_eq!(rotations.len(), 24);
+        round_trip::<3>(&config);
+    }
+
+    #[test]
+    fn harness() {
+        for seed in 0..10 {
+            round_trip::<3>(&WorldConfig {
+                seed,
+                ..WorldConfig::default()
+            });
+        }
+        // sparse worlds with the bare minimum of overlap.
+        for seed in 0..5 {
+            round_trip::<3>(&WorldConfig {
+                seed,
+                scanners: 10,
+                beacons: 12,
+                ..WorldConfig::default()
+            });
+        }
+        // a lower threshold, which relies more on checking that nothing is missing.
+        for seed in 0..5 {
+            round_trip::<3>(&WorldConfig {
+                seed,
+                scanners: 20,
+                beacons: 15,
+                overlap: 6,
+                ..WorldConfig::default()
+            });
+        }
+        // and big ones that wander well outside an i16.
+        round_trip::<3>(&WorldConfig {
+            seed: 35,
+            scanners: 60,
+            range: 20000,
+            ..WorldConfig::default()
+        });
+    }
+
+    #[test]
+    fn other_dimensions() {
+        // flat scanners, which can be turned over, so all 8 orientations show up.
+        let config = WorldConfig {
+            scanners: 40,
+            ..WorldConfig::default()
+        };
+        let world = World::<2>::generate(&config).unwrap();
+        let rotations = world
+            .poses
+            .iter()
+            .map(|p| p.rotation)
+            .collect::<HashSet<_>>();
+        assert_eq!(rotations.len(), 8);
+        for seed in 0..5 {
+            round_trip::<2>(&WorldConfig {
+                seed,
+                scanners: 20,
+                ..WorldConfig::default()
+            });
+        }
+        for seed in 0..3 {
+            round_trip::<4>(&WorldConfig {
+                seed,
+                scanners: 10,
+                ..WorldConfig::default()
+            });
+        }
+        // a line of scanners.
+        round_trip::<1>(&WorldConfig {
+            scanners: 5,
+            ..WorldConfig::default()
+        });
+    }
+}
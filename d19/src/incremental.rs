@@ -0,0 +1,236 @@
+//! aligning scanners one at a time, as they come in, rather than all at once.
+
+use std::collections::VecDeque;
+
+use log::trace;
+
+use crate::{align::Match, AlignConfig, Alignment, BeaconMap, Fingerprint, Pose, Scanner};
+
+/// a world which grows as scanners are `push`ed into it. the first scanner pushed is the origin, whatever its id, and scanners are referred to by the order they were pushed in. scanners which can't be placed when they arrive wait until one they overlap is.
+#[derive(Clone, Debug, Default)]
+pub struct IncrementalAligner<const N: usize = 3> {
+    config: AlignConfig,
+    scanners: Vec<Scanner<N>>,
+    fingerprints: Vec<Fingerprint>,
+    poses: Vec<Option<Pose<N>>>,
+    matches: Vec<Match<N>>,
+    map: BeaconMap<N>,
+    /// the indices of the scanners which haven't been placed yet, in the order they arrived.
+    pending: Vec<usize>,
+}
+
+impl<const N: usize> IncrementalAligner<N> {
+    pub fn new() -> Self {
+        Self::with_config(AlignConfig::default())
+    }
+
+    pub fn with_config(config: AlignConfig) -> Self {
+        Self {
+            config,
+            scanners: Vec::new(),
+            fingerprints: Vec::new(),
+            poses: Vec::new(),
+            matches: Vec::new(),
+            map: BeaconMap::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// add a scanner, and place it if it overlaps one that's already placed. if it is, any waiting scanners which overlap it are placed too, and so on. returns the indices of every scanner placed along the way, in the order they were placed, which is empty if `scanner` has to wait.
+    pub fn push(&mut self, scanner: Scanner<N>) -> Vec<usize> {
+        let index = self.scanners.len();
+        self.fingerprints.push(Fingerprint::new(&scanner));
+        self.scanners.push(scanner);
+        self.poses.push(None);
+
+        if index == 0 {
+            self.place(index, Pose::ORIGIN);
+            return vec![index];
+        }
+
+        let base = (0..index).find_map(|base| {
+            self.poses[base]?;
+            self.try_match(base, index)
+        });
+        let Some(m) = base else {
+            trace!("scanner {index} has to wait");
+            self.pending.push(index);
+            return Vec::new();
+        };
+        self.accept(m);
+
+        // newly placed scanners might be what some of the waiting ones were waiting for.
+        let mut placed = vec![index];
+        let mut queue = VecDeque::from([index]);
+        while let Some(base) = queue.pop_front() {
+            let mut i = 0;
+            while i < self.pending.len() {
+                let other = self.pending[i];
+                if let Some(m) = self.try_match(base, other) {
+                    self.pending.remove(i);
+                    self.accept(m);
+                    placed.push(other);
+                    queue.push_back(other);
+                } else {
+                    i += 1;
+                }
+            }
+        }
+        placed
+    }
+
+    /// match `other` against `base`, which must be placed.
+    fn try_match(&self, base: usize, other: usize) -> Option<Match<N>> {
+        if !self.fingerprints[base].plausible(&self.fingerprints[other], self.config.min_overlap) {
+            return None;
+        }
+        let (rotation, displacement) =
+            self.scanners[base].diff_with(&self.scanners[other], &self.config)?;
+        Some(Match {
+            base,
+            other,
+            rotation,
+            displacement,
+        })
+    }
+
+    fn accept(&mut self, m: Match<N>) {
+        let pose = self.poses[m.base]
+            .expect("matches are against placed scanners")
+            .then(&m.rotation, m.displacement);
+        trace!(
+            "scanner {} placed at {pose} from scanner {}",
+            m.other,
+            m.base
+        );
+        self.matches.push(m);
+        self.place(m.other, pose);
+    }
+
+    fn place(&mut self, index: usize, pose: Pose<N>) {
+        self.poses[index] = Some(pose);
+        self.map.add_scanner(&self.scanners[index], &pose);
+    }
+
+    /// every scanner pushed so far, in the order they arrived.
+    pub fn scanners(&self) -> &[Scanner<N>] {
+        &self.scanners
+    }
+
+    /// the pose of each scanner, by index, relative to the first.
+    pub fn poses(&self) -> &[Option<Pose<N>>] {
+        &self.poses
+    }
+
+    /// the indices of the scanners waiting to be placed, in the order they arrived.
+    pub fn pending(&self) -> &[usize] {
+        &self.pending
+    }
+
+    /// every beacon seen by a placed scanner so far, relative to the first scanner.
+    pub fn map(&self) -> &BeaconMap<N> {
+        &self.map
+    }
+
+    /// the world as it stands. beacons are in the order they were placed, which isn't necessarily the order `align` would give.
+    pub fn alignment(&self) -> Alignment<N> {
+        Alignment {
+            poses: self.poses.clone(),
+            beacons: self.map.beacons().to_vec(),
+            matches: self.matches.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use common::rng::Rng;
+
+    use super::*;
+    use crate::{
+        align, align_with,
+        gen::{World, WorldConfig},
+    };
+
+    #[test]
+    fn example() {
+        let scanners = crate::tests::example();
+        let mut aligner = IncrementalAligner::new();
+        let placed = scanners
+            .iter()
+            .map(|s| aligner.push(s.clone()))
+            .collect::<Vec<_>>();
+        // 2 only overlaps 4, so it has to wait for it.
+        assert_eq!(placed, [vec![0], vec![1], vec![], vec![3], vec![4, 2]]);
+        assert!(aligner.pending().is_empty());
+
+        let alignment = aligner.alignment();
+        assert_eq!(alignment.poses, align(&scanners).poses);
+        assert_eq!(alignment.beacons.len(), 79);
+        assert_eq!(alignment.max_distance(), 3621);
+        assert_eq!(aligner.map().len(), 79);
+    }
+
+    #[test]
+    fn partial() {
+        let scanners = crate::tests::example();
+        let mut aligner = IncrementalAligner::new();
+        assert_eq!(aligner.push(scanners[0].clone()), [0]);
+        assert!(aligner.push(scanners[2].clone()).is_empty());
+        assert_eq!(aligner.pending(), [1]);
+        // the world so far is just scanner 0.
+        let alignment = aligner.alignment();
+        assert_eq!(alignment.unreachable(), [1]);
+        assert_eq!(alignment.beacons, scanners[0].beacons);
+
+        assert!(aligner.push(scanners[4].clone()).is_empty());
+        assert_eq!(aligner.pending(), [1, 2]);
+        // 1 overlaps 0 and 4, and then 4 overlaps 2.
+        assert_eq!(aligner.push(scanners[1].clone()), [3, 2, 1]);
+        assert!(aligner.pending().is_empty());
+        // without 3 there are a few beacons missing, but nothing that isn't there.
+        let full = align(&scanners).beacons.into_iter().collect::<HashSet<_>>();
+        assert!(aligner.map().len() < full.len());
+        assert!(aligner.map().beacons().iter().all(|b| full.contains(b)));
+    }
+
+    #[test]
+    fn shuffled() {
+        let config = WorldConfig {
+            seed: 45,
+            scanners: 15,
+            ..WorldConfig::default()
+        };
+        let world: World = World::generate(&config).unwrap();
+        let mut rng = Rng::new(45);
+        for _ in 0..3 {
+            let mut scanners = world.scanners.clone();
+            rng.shuffle(&mut scanners);
+
+            let mut aligner = IncrementalAligner::new();
+            let mut placed = Vec::new();
+            for (i, scanner) in scanners.iter().enumerate() {
+                placed.extend(aligner.push(scanner.clone()));
+                // everything is either placed or waiting.
+                assert_eq!(
+                    aligner.poses().iter().flatten().count() + aligner.pending().len(),
+                    i + 1
+                );
+            }
+            assert_eq!(placed.iter().collect::<HashSet<_>>().len(), scanners.len());
+            assert!(aligner.pending().is_empty());
+
+            // the same as aligning them all at once in the order they arrived.
+            let alignment = aligner.alignment();
+            let all_at_once = align_with(&scanners, &AlignConfig::default());
+            assert_eq!(alignment.poses, all_at_once.poses);
+            assert_eq!(
+                alignment.beacons.iter().collect::<HashSet<_>>(),
+                all_at_once.beacons.iter().collect::<HashSet<_>>()
+            );
+            assert_eq!(alignment.beacons.len(), world.beacons.len());
+        }
+    }
+}
@@ -1,288 +1,684 @@
+pub mod align;
+pub mod approx;
+pub mod diagnostics;
+pub mod distance;
+pub mod export;
+pub mod fingerprint;
+pub mod gen;
+pub mod incremental;
+pub mod map;
+pub mod parallel;
+
+pub use align::{align, align_with, Alignment, Pose};
+pub use fingerprint::Fingerprint;
+pub use incremental::IncrementalAligner;
+pub use map::BeaconMap;
+
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt::Display,
-    ops::{Add, AddAssign, Sub},
-    ptr,
+    io::{BufRead, Lines},
+    ops::{Add, AddAssign, Neg, Sub},
     str::FromStr,
 };
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 
 use log::trace;
 
-use derive_more::Display;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+/// the number of beacons two scanners must have in common before they're considered to overlap.
+pub const MIN_OVERLAP: usize = 12;
+
+/// how far scanners can see along each axis.
+pub const RANGE: Coord = 1000;
 
-pub const MIN_OVERLAP: u8 = 12;
+/// how strict to be about deciding that two scanners overlap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AlignConfig {
+    /// how many beacons two scanners must agree on.
+    pub min_overlap: usize,
+    /// how far scanners can see along each axis. if this is `Some`, a match is only accepted if each scanner sees every beacon of the other's which is inside its range. if it's `None`, scanners are assumed to see forever, and matches aren't checked.
+    pub range: Option<Coord>,
+}
 
-#[derive(Default, PartialEq, Eq, Clone, Copy, Debug, Display)]
-#[display(fmt = "{},{},{}", x, y, z)]
-pub struct Point {
-    x: i16,
-    y: i16,
-    z: i16,
+impl Default for AlignConfig {
+    fn default() -> Self {
+        Self {
+            min_overlap: MIN_OVERLAP,
+            range: Some(RANGE),
+        }
+    }
+}
+
+/// the type of a single coordinate. arithmetic on points and displacements panics if it would overflow this, rather than wrapping.
+pub type Coord = i32;
+
+const OVERFLOW: &str = "coordinate overflow";
+
+/// the names of the first few axes, for `Rotation`'s `Display`. any after these are written as their index.
+const AXES: [char; 4] = ['x', 'y', 'z', 'w'];
+
+fn fmt_coords(f: &mut std::fmt::Formatter<'_>, coords: &[Coord]) -> std::fmt::Result {
+    for (i, n) in coords.iter().enumerate() {
+        if i > 0 {
+            write!(f, ",")?;
+        }
+        write!(f, "{n}")?;
+    }
+    Ok(())
 }
 
-impl FromStr for Point {
+/// a point in `N` dimensions. the puzzle's are in 3, which is the default.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub struct Point<const N: usize = 3>([Coord; N]);
+
+impl<const N: usize> Default for Point<N> {
+    fn default() -> Self {
+        Self::ORIGIN
+    }
+}
+
+impl<const N: usize> Display for Point<N> {
+    /// e.g. `1,-2,3`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_coords(f, &self.0)
+    }
+}
+
+impl<const N: usize> FromStr for Point<N> {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self> {
-        let mut pt = Self::default();
+        let mut pt = Self::ORIGIN;
         let mut ns = s.split(',');
-        pt.x = ns.next().context("expected comma")?.parse::<i16>()?;
-        pt.y = ns.next().context("expected comma")?.parse::<i16>()?;
-        pt.z = ns.next().context("expected comma")?.parse::<i16>()?;
+        for coord in &mut pt.0 {
+            *coord = ns.next().context("expected comma")?.parse::<Coord>()?;
+        }
+        if ns.next().is_some() {
+            bail!("expected {N} coordinates, found more");
+        }
         Ok(pt)
     }
 }
 
-impl Sub<Self> for Point {
-    type Output = Displacement;
+impl<const N: usize> From<[Coord; N]> for Point<N> {
+    fn from(coords: [Coord; N]) -> Self {
+        Self(coords)
+    }
+}
+
+/// how a 3d point looks in serde formats.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Point")]
+struct Xyz {
+    x: Coord,
+    y: Coord,
+    z: Coord,
+}
+
+impl Serialize for Point<3> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let [x, y, z] = self.0;
+        Xyz { x, y, z }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Point<3> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let Xyz { x, y, z } = Xyz::deserialize(deserializer)?;
+        Ok(Self::new(x, y, z))
+    }
+}
+
+impl<const N: usize> Sub<Self> for Point<N> {
+    type Output = Displacement<N>;
 
     fn sub(self, rhs: Self) -> Self::Output {
-        Displacement {
-            x: self.x - rhs.x,
-            y: self.y - rhs.y,
-            z: self.z - rhs.z,
-        }
+        self.checked_sub(rhs).expect(OVERFLOW)
     }
 }
 
-impl Add<Displacement> for Point {
+impl<const N: usize> Add<Displacement<N>> for Point<N> {
     type Output = Self;
 
-    fn add(self, rhs: Displacement) -> Self::Output {
-        Self {
-            x: rhs.x + self.x,
-            y: rhs.y + self.y,
-            z: rhs.z + self.z,
-        }
+    fn add(self, rhs: Displacement<N>) -> Self::Output {
+        self.checked_add(rhs).expect(OVERFLOW)
     }
 }
 
-impl AddAssign<Displacement> for Point {
-    fn add_assign(&mut self, rhs: Displacement) {
-        self.x += rhs.x;
-        self.y += rhs.y;
-        self.z += rhs.z;
+impl<const N: usize> AddAssign<Displacement<N>> for Point<N> {
+    fn add_assign(&mut self, rhs: Displacement<N>) {
+        *self = *self + rhs;
     }
 }
 
-impl Point {
-    pub const fn new(x: i16, y: i16, z: i16) -> Self {
-        Self { x, y, z }
+impl Point<3> {
+    pub const fn new(x: Coord, y: Coord, z: Coord) -> Self {
+        Self([x, y, z])
     }
+}
 
-    pub fn get_mut(&mut self, dim: Dim) -> &mut i16 {
-        match dim {
-            Dim::X => &mut self.x,
-            Dim::Y => &mut self.y,
-            Dim::Z => &mut self.z,
-        }
-    }
+impl<const N: usize> Point<N> {
+    pub const ORIGIN: Self = Self([0; N]);
 
-    pub fn swap(&mut self, swap: &Swap) {
-        // SAFETY: all ptrs are cast from references, and so are valid and aligned.
-        match swap {
-            Swap::None => (),
-            Swap::Once(a, b) => unsafe { ptr::swap(self.get_mut(*a), self.get_mut(*b)) },
-            Swap::Twice(a, b, c) => unsafe {
-                ptr::swap(self.get_mut(*a), self.get_mut(*b));
-                ptr::swap(self.get_mut(*b), self.get_mut(*c));
-            },
-        }
+    pub const fn coords(&self) -> [Coord; N] {
+        self.0
     }
 
-    pub fn unswap(&mut self, swap: &Swap) {
-        // SAFETY: all ptrs are cast from references, and so are valid and aligned.
-        match swap {
-            Swap::None => (),
-            Swap::Once(a, b) => unsafe { ptr::swap(self.get_mut(*a), self.get_mut(*b)) },
-            Swap::Twice(a, b, c) => unsafe {
-                ptr::swap(self.get_mut(*b), self.get_mut(*c));
-                ptr::swap(self.get_mut(*a), self.get_mut(*b));
-            },
+    /// `self + dsp`, or `None` if any coordinate would overflow.
+    pub const fn checked_add(self, dsp: Displacement<N>) -> Option<Self> {
+        match add_raw(self.0, dsp.0) {
+            Some(coords) => Some(Self(coords)),
+            None => None,
         }
     }
 
-    pub fn refl(&mut self, refl: &Reflect) {
-        if refl.x {
-            self.x *= -1;
-        }
-        if refl.y {
-            self.y *= -1;
-        }
-        if refl.z {
-            self.z *= -1;
+    /// `self - other`, or `None` if any coordinate would overflow.
+    pub const fn checked_sub(self, other: Self) -> Option<Displacement<N>> {
+        match sub_raw(self.0, other.0) {
+            Some(coords) => Some(Displacement(coords)),
+            None => None,
         }
     }
 
-    pub fn rotate(&mut self, Rotation(swap, refl): &Rotation) {
-        self.swap(swap);
-        self.refl(refl);
+    pub fn rotate(&mut self, rot: &Rotation<N>) {
+        *self = rot.apply(*self);
     }
 
-    pub fn unrotate(&mut self, Rotation(swap, refl): &Rotation) {
-        self.refl(refl);
-        self.unswap(swap);
+    pub fn unrotate(&mut self, rot: &Rotation<N>) {
+        *self = rot.inverse().apply(*self);
     }
 
     /// return the squared euclidean distance between `self` and `other`.
-    pub const fn dist_squclid(&self, rhs: &Self) -> u32 {
-        // (1000 - (-1000)).pow(2) == 4000000
-        let xd = (self.x.abs_diff(rhs.x) as u32).pow(2);
-        let yd = (self.y.abs_diff(rhs.y) as u32).pow(2);
-        let zd = (self.z.abs_diff(rhs.z) as u32).pow(2);
-        // 4000000 * 3 == 12000000 < u32::MAX
-        xd + yd + zd
+    pub const fn dist_squclid(&self, rhs: &Self) -> u64 {
+        // each difference fits in a u32, so its square fits in a u64, but several of them might not.
+        let mut diffs = [0; N];
+        let mut i = 0;
+        while i < N {
+            diffs[i] = self.0[i].abs_diff(rhs.0[i]);
+            i += 1;
+        }
+        squclid(diffs)
     }
 
     /// return the squared euclidean distance between `self` and the origin.
-    pub const fn abs_squclid(&self) -> u32 {
-        (self.x.unsigned_abs() as u32).pow(2)
-            + (self.y.unsigned_abs() as u32).pow(2)
-            + (self.z.unsigned_abs() as u32).pow(2)
+    pub const fn abs_squclid(&self) -> u64 {
+        self.dist_squclid(&Self::ORIGIN)
     }
 
-    pub const fn dist_taxicab(&self, rhs: &Self) -> u16 {
-        self.x.abs_diff(rhs.x) + self.y.abs_diff(rhs.y) + self.z.abs_diff(rhs.z)
+    pub const fn dist_taxicab(&self, rhs: &Self) -> u64 {
+        // a u64 can hold billions of u32s, so this can't overflow.
+        let mut res = 0;
+        let mut i = 0;
+        while i < N {
+            res += self.0[i].abs_diff(rhs.0[i]) as u64;
+            i += 1;
+        }
+        res
     }
 
     /// return the taxicab distance between `self` and the origin.
-    pub const fn abs_taxicab(&self) -> u16 {
-        self.x.unsigned_abs() + self.y.unsigned_abs() + self.z.unsigned_abs()
+    pub const fn abs_taxicab(&self) -> u64 {
+        self.dist_taxicab(&Self::ORIGIN)
     }
 
-    // TODO: make this work for edge cases
-    /// assuming that these points have the same euclidean distance from the origin, and that they have already been `unswap`ped according to `swap_diff`, find what `Reflect`ion they differ by.
-    /// if one of the dimensions is zero, this will always say that that dimension was not reflected.
-    pub const fn refl_diff(&self, other: Self) -> Reflect {
-        Reflect {
-            x: self.x.signum() != other.x.signum(),
-            y: self.y.signum() != other.y.signum(),
-            z: self.z.signum() != other.z.signum(),
+    /// the greatest difference between `self` and `other` along any axis.
+    pub const fn dist_chebyshev(&self, rhs: &Self) -> u64 {
+        let mut res = 0;
+        let mut i = 0;
+        while i < N {
+            let d = self.0[i].abs_diff(rhs.0[i]) as u64;
+            if d > res {
+                res = d;
+            }
+            i += 1;
         }
+        res
     }
 
-    // TODO: make this work for edge cases
-    /// assuming that these points have the same euclidean distance from the origin, find what `Swap` could be perfomed to transform `self` into `other`.
-    /// if two of the dimensions are the same, this will only output one of the possible swaps that the points differ by.
-    pub const fn swap_diff(&self, other: Self) -> Swap {
-        if abs_eq(self.x, other.x) {
-            if abs_eq(self.y, other.y) && abs_eq(self.z, other.z) {
-                Swap::None
-            } else {
-                Swap::Once(Dim::Y, Dim::Z)
+    /// all of the `Rotation`s which would turn `self` into `other`, in the order of `Rotation::all`. there is exactly one if no coordinate of `self` is zero and no two have the same magnitude (and, in 3 dimensions or more, half the time there are none), and there are more otherwise.
+    pub fn rots_between(self, other: Self) -> impl Iterator<Item = Rotation<N>> {
+        let mut res = Vec::new();
+        let mut rot = Rotation::IDENTITY;
+        rots_between(&self.0, &other.0, &mut rot, &mut [false; N], 0, &mut res);
+        res.into_iter()
+    }
+}
+
+/// the guts of `Point::rots_between`: fill in `rot`'s axes from `row` on, trying each unused axis of `from` which is the right size, in order, and then work out the signs.
+fn rots_between<const N: usize>(
+    from: &[Coord; N],
+    to: &[Coord; N],
+    rot: &mut Rotation<N>,
+    used: &mut [bool; N],
+    row: usize,
+    res: &mut Vec<Rotation<N>>,
+) {
+    if row < N {
+        for col in 0..N {
+            if !used[col] && from[col].unsigned_abs() == to[row].unsigned_abs() {
+                used[col] = true;
+                rot.axes[row] = col;
+                rots_between(from, to, rot, used, row + 1, res);
+                used[col] = false;
             }
-        } else if abs_eq(self.y, other.y) {
-            Swap::Once(Dim::X, Dim::Z)
-        } else if abs_eq(self.z, other.z) {
-            Swap::Once(Dim::X, Dim::Y)
-        } else if abs_eq(self.x, other.z) {
-            Swap::Twice(Dim::X, Dim::Y, Dim::Z)
+        }
+        return;
+    }
+
+    // the sign of a row which takes a zero to a zero could be either, and counting up through those in binary keeps to the order of `Rotation::all`.
+    let free = (0..N).filter(|&row| to[row] == 0).collect::<Vec<_>>();
+    for row in 0..N {
+        rot.signs[row] = if to[row].signum() == from[rot.axes[row]].signum() {
+            1
         } else {
-            Swap::Twice(Dim::Z, Dim::Y, Dim::X)
+            -1
+        };
+    }
+    for mask in 0..1_u64 << free.len() {
+        for (bit, &row) in free.iter().enumerate() {
+            rot.signs[row] = if mask >> bit & 1 == 0 { 1 } else { -1 };
+        }
+        if rot.allowed() {
+            res.push(*rot);
         }
     }
+}
+
+const fn squclid<const N: usize>(diffs: [u32; N]) -> u64 {
+    let mut res: u64 = 0;
+    let mut i = 0;
+    while i < N {
+        let sq = diffs[i] as u64 * diffs[i] as u64;
+        res = match res.checked_add(sq) {
+            Some(n) => n,
+            None => panic!("squared distance overflow"),
+        };
+        i += 1;
+    }
+    res
+}
 
-    /// assuming it can be done, return the `Rotation` which would turn `self` into `other`.
-    pub fn rot_diff(mut self, other: Self) -> Rotation {
-        let swap = self.swap_diff(other);
-        self.swap(&swap);
-        let refl = self.refl_diff(other);
-        Rotation(swap, refl)
+const fn add_raw<const N: usize>(a: [Coord; N], b: [Coord; N]) -> Option<[Coord; N]> {
+    let mut res = [0; N];
+    let mut i = 0;
+    while i < N {
+        res[i] = match a[i].checked_add(b[i]) {
+            Some(n) => n,
+            None => return None,
+        };
+        i += 1;
     }
+    Some(res)
 }
 
-const fn abs_eq(lhs: i16, rhs: i16) -> bool {
-    lhs == rhs || lhs + rhs == 0
+const fn sub_raw<const N: usize>(a: [Coord; N], b: [Coord; N]) -> Option<[Coord; N]> {
+    let mut res = [0; N];
+    let mut i = 0;
+    while i < N {
+        res[i] = match a[i].checked_sub(b[i]) {
+            Some(n) => n,
+            None => return None,
+        };
+        i += 1;
+    }
+    Some(res)
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Display)]
-pub enum Dim {
-    X,
-    Y,
-    Z,
+/// one of the ways a scanner can be oriented: an NxN matrix with a single `1` or `-1` in each row and column. in 3 dimensions or more, only the proper rotations count, with a determinant of 1, which makes 24 in 3d and 192 in 4d. a flat scanner can still be turned over, so in 2d all 8 count.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Rotation<const N: usize = 3> {
+    /// row `i` of the matrix has its nonzero entry in column `axes[i]`,
+    axes: [usize; N],
+    /// and that entry is `signs[i]`.
+    signs: [i8; N],
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Display)]
-#[display(
-    fmt = "{}{}{}",
-    r#"if *x { "-" } else { "+" }"#,
-    r#"if *y { "-" } else { "+" }"#,
-    r#"if *z { "-" } else { "+" }"#,
-)]
-pub struct Reflect {
-    pub x: bool,
-    pub y: bool,
-    pub z: bool,
+impl<const N: usize> TryFrom<[[i8; N]; N]> for Rotation<N> {
+    type Error = anyhow::Error;
+
+    fn try_from(m: [[i8; N]; N]) -> Result<Self> {
+        Self::new(m).with_context(|| format!("{m:?} isn't a rotation matrix"))
+    }
 }
 
-impl Reflect {
-    pub const fn along(dim: Dim) -> Self {
-        Self {
-            x: matches!(dim, Dim::X),
-            y: matches!(dim, Dim::Y),
-            z: matches!(dim, Dim::Z),
+impl<const N: usize> From<Rotation<N>> for [[i8; N]; N] {
+    fn from(rot: Rotation<N>) -> Self {
+        rot.matrix()
+    }
+}
+
+impl<const N: usize> Serialize for Rotation<N> {
+    /// as its matrix, one row at a time.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.matrix().iter().map(|row| row.as_slice()))
+    }
+}
+
+impl<'de, const N: usize> Deserialize<'de> for Rotation<N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let rows = Vec::<Vec<i8>>::deserialize(deserializer)?;
+        let mut m = [[0; N]; N];
+        if rows.len() != N || rows.iter().any(|row| row.len() != N) {
+            return Err(D::Error::custom(format!("expected a {N}x{N} matrix")));
+        }
+        for (row, entries) in m.iter_mut().zip(rows) {
+            row.copy_from_slice(&entries);
         }
+        Self::try_from(m).map_err(D::Error::custom)
     }
+}
 
-    pub const fn total(&self) -> u8 {
-        self.x as u8 + self.y as u8 + self.z as u8
+impl<const N: usize> FromStr for Rotation<N> {
+    type Err = anyhow::Error;
+
+    /// the inverse of `Display`, e.g. `+z-y+x`.
+    fn from_str(s: &str) -> Result<Self> {
+        let mut m = [[0; N]; N];
+        let mut rest = s;
+        for row in &mut m {
+            let sign = match rest.as_bytes().first() {
+                Some(b'+') => 1,
+                Some(b'-') => -1,
+                _ => bail!(
+                    "bad rotation `{s}`: expected {N} signed axes, like `{}`",
+                    Self::IDENTITY
+                ),
+            };
+            rest = &rest[1..];
+            let (axis, tail) = rest.split_at(rest.find(['+', '-']).unwrap_or(rest.len()));
+            let col = match AXES
+                .iter()
+                .position(|c| axis.len() == 1 && axis.starts_with(*c))
+            {
+                Some(col) => col,
+                None => axis
+                    .parse()
+                    .with_context(|| format!("bad rotation `{s}`: no axis `{axis}`"))?,
+            };
+            if col >= N {
+                bail!("bad rotation `{s}`: no axis `{axis}` in {N} dimensions");
+            }
+            row[col] = sign;
+            rest = tail;
+        }
+        if !rest.is_empty() {
+            bail!("bad rotation `{s}`: expected {N} axes, found more");
+        }
+        Self::try_from(m).with_context(|| format!("bad rotation `{s}`"))
+    }
+}
+
+impl<const N: usize> Display for Rotation<N> {
+    /// displays which axis of the original point each axis of the rotated point comes from, e.g. `+z-y+x`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (&axis, &sign) in self.axes.iter().zip(&self.signs) {
+            write!(f, "{}", if sign > 0 { '+' } else { '-' })?;
+            match AXES.get(axis) {
+                Some(name) => write!(f, "{name}")?,
+                None => write!(f, "{axis}")?,
+            }
+        }
+        Ok(())
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Display)]
-pub enum Swap {
-    #[display(fmt = "none")]
-    None,
-    #[display(fmt = "{}<>{}", _0, _1)]
-    Once(Dim, Dim),
-    #[display(fmt = "{}<>{}<>{}", _0, _1, _2)]
-    Twice(Dim, Dim, Dim),
+impl Rotation<3> {
+    /// every proper rotation, always in the same order: by permutation of the axes, then by signs. `IDENTITY` comes first.
+    pub const ALL: [Self; 24] = Self::enumerate();
 }
 
-impl Swap {
-    pub const fn total(&self) -> u8 {
-        match self {
-            Self::None => 0,
-            Self::Once(_, _) => 1,
-            Self::Twice(_, _, _) => 2,
+impl<const N: usize> Rotation<N> {
+    pub const IDENTITY: Self = Self {
+        axes: identity_perm(),
+        signs: [1; N],
+    };
+
+    /// every rotation in `N` dimensions, always in the same order: by permutation of the axes, then by signs. `IDENTITY` comes first.
+    pub fn all() -> impl Iterator<Item = Self> {
+        std::iter::successors(Some(Self::IDENTITY), Self::next)
+    }
+
+    /// `all`, as an array, for when `M` is known to be the number of rotations.
+    const fn enumerate<const M: usize>() -> [Self; M] {
+        let mut res = [Self::IDENTITY; M];
+        let mut n = 1;
+        let mut rot = Self::IDENTITY;
+        while let Some(next) = rot.next() {
+            assert!(n < M, "more rotations than expected");
+            res[n] = next;
+            rot = next;
+            n += 1;
+        }
+        assert!(n == M, "fewer rotations than expected");
+        res
+    }
+
+    /// the rotation after `self` in the order of `all`, if there is one.
+    const fn next(&self) -> Option<Self> {
+        let mut rot = *self;
+        loop {
+            // count up in binary through the signs, with row 0 as the lowest bit and -1 as a 1.
+            let mut row = 0;
+            while row < N && rot.signs[row] < 0 {
+                rot.signs[row] = 1;
+                row += 1;
+            }
+            if row < N {
+                rot.signs[row] = -1;
+            } else if !next_perm(&mut rot.axes) {
+                return None;
+            }
+            if rot.allowed() {
+                return Some(rot);
+            }
         }
     }
+
+    /// whether this is one of the orientations a scanner can actually be in.
+    const fn allowed(&self) -> bool {
+        N < 3 || self.det() == 1
+    }
+
+    /// construct a rotation from its matrix. returns `None` if `m` isn't one of the ones in `all`.
+    pub fn new(m: [[i8; N]; N]) -> Option<Self> {
+        let mut rot = Self::IDENTITY;
+        let mut used = [false; N];
+        for (row, entries) in m.iter().enumerate() {
+            let mut nonzero = entries.iter().enumerate().filter(|&(_, &n)| n != 0);
+            let (col, &sign) = nonzero.next()?;
+            if nonzero.next().is_some() || !matches!(sign, 1 | -1) || used[col] {
+                return None;
+            }
+            used[col] = true;
+            rot.axes[row] = col;
+            rot.signs[row] = sign;
+        }
+        rot.allowed().then_some(rot)
+    }
+
+    pub const fn matrix(&self) -> [[i8; N]; N] {
+        let mut m = [[0; N]; N];
+        let mut row = 0;
+        while row < N {
+            m[row][self.axes[row]] = self.signs[row];
+            row += 1;
+        }
+        m
+    }
+
+    /// the product of the signs, and -1 for every pair of axes the permutation puts out of order.
+    pub const fn det(&self) -> i8 {
+        let mut det = 1;
+        let mut i = 0;
+        while i < N {
+            det *= self.signs[i];
+            let mut j = i + 1;
+            while j < N {
+                if self.axes[i] > self.axes[j] {
+                    det = -det;
+                }
+                j += 1;
+            }
+            i += 1;
+        }
+        det
+    }
+
+    pub const fn apply(&self, pt: Point<N>) -> Point<N> {
+        Point(self.apply_raw(pt.0))
+    }
+
+    pub const fn apply_dsp(&self, dsp: Displacement<N>) -> Displacement<N> {
+        Displacement(self.apply_raw(dsp.0))
+    }
+
+    const fn apply_raw(&self, v: [Coord; N]) -> [Coord; N] {
+        let mut res = [0; N];
+        let mut row = 0;
+        while row < N {
+            // each row is a copy of one coordinate, maybe negated. negating `Coord::MIN` is the only way it can overflow.
+            let n = v[self.axes[row]];
+            res[row] = if self.signs[row] > 0 {
+                n
+            } else {
+                match n.checked_neg() {
+                    Some(n) => n,
+                    None => panic!("coordinate overflow"),
+                }
+            };
+            row += 1;
+        }
+        res
+    }
+
+    /// the rotation which does `other` and then `self`.
+    #[must_use]
+    pub const fn compose(&self, other: &Self) -> Self {
+        let mut res = Self::IDENTITY;
+        let mut row = 0;
+        while row < N {
+            let mid = self.axes[row];
+            res.axes[row] = other.axes[mid];
+            res.signs[row] = self.signs[row] * other.signs[mid];
+            row += 1;
+        }
+        res
+    }
+
+    /// the rotation which undoes `self`. since rotation matrices are orthogonal, this is just the transpose.
+    #[must_use]
+    pub const fn inverse(&self) -> Self {
+        let mut res = Self::IDENTITY;
+        let mut row = 0;
+        while row < N {
+            res.axes[self.axes[row]] = row;
+            res.signs[self.axes[row]] = self.signs[row];
+            row += 1;
+        }
+        res
+    }
+}
+
+const fn identity_perm<const N: usize>() -> [usize; N] {
+    let mut res = [0; N];
+    let mut i = 0;
+    while i < N {
+        res[i] = i;
+        i += 1;
+    }
+    res
+}
+
+/// step `perm` on to the next permutation in lexicographic order, or return `false` if it's already the last.
+const fn next_perm<const N: usize>(perm: &mut [usize; N]) -> bool {
+    // the start of the longest decreasing run at the end.
+    let mut i = N;
+    while i > 1 && perm[i - 2] >= perm[i - 1] {
+        i -= 1;
+    }
+    if i <= 1 {
+        return false;
+    }
+    let pivot = i - 2;
+    let mut j = N - 1;
+    while perm[j] <= perm[pivot] {
+        j -= 1;
+    }
+    let tmp = perm[pivot];
+    perm[pivot] = perm[j];
+    perm[j] = tmp;
+
+    let (mut a, mut b) = (pivot + 1, N - 1);
+    while a < b {
+        let tmp = perm[a];
+        perm[a] = perm[b];
+        perm[b] = tmp;
+        a += 1;
+        b -= 1;
+    }
+    true
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub struct Displacement<const N: usize = 3>([Coord; N]);
+
+impl<const N: usize> Default for Displacement<N> {
+    fn default() -> Self {
+        Self([0; N])
+    }
+}
+
+impl<const N: usize> Display for Displacement<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_coords(f, &self.0)
+    }
+}
+
+impl<const N: usize> From<[Coord; N]> for Displacement<N> {
+    fn from(coords: [Coord; N]) -> Self {
+        Self(coords)
+    }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Display)]
-#[display(fmt = "{} {}", _0, _1)]
-pub struct Rotation(pub Swap, pub Reflect);
+impl<const N: usize> Neg for Displacement<N> {
+    type Output = Self;
 
-impl Rotation {
-    pub const fn is_valid(&self) -> bool {
-        (self.0.total() + self.1.total()) % 2 == 0
+    fn neg(self) -> Self::Output {
+        Self(sub_raw([0; N], self.0).expect(OVERFLOW))
     }
 }
 
-#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash, Display, Default)]
-#[display(fmt = "{},{},{}", x, y, z)]
-pub struct Displacement {
-    x: i16,
-    y: i16,
-    z: i16,
+impl Displacement<3> {
+    pub const fn new(x: Coord, y: Coord, z: Coord) -> Self {
+        Self([x, y, z])
+    }
 }
 
-impl Displacement {
-    pub const fn dist_taxicab(&self) -> i16 {
-        self.x + self.y + self.z
+impl<const N: usize> Displacement<N> {
+    pub const fn coords(&self) -> [Coord; N] {
+        self.0
+    }
+
+    /// the taxicab length of the displacement.
+    pub const fn dist_taxicab(&self) -> u64 {
+        Point(self.0).abs_taxicab()
     }
 }
 
-pub struct Scanner {
-    pub beacons: Vec<Point>,
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Scanner<const N: usize = 3> {
+    /// the number from the scanner's `--- scanner N ---` header.
+    pub id: usize,
+    pub beacons: Vec<Point<N>>,
 }
 
-impl Display for Scanner {
+impl<const N: usize> Display for Scanner<N> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if !f.alternate() {
-            writeln!(f, "--- scanner ---")?;
+            writeln!(f, "--- scanner {} ---", self.id)?;
         }
         for beacon in &self.beacons {
             writeln!(f, "{}", beacon)?;
@@ -292,43 +688,92 @@ impl Display for Scanner {
     }
 }
 
-impl AddAssign<Displacement> for Scanner {
-    fn add_assign(&mut self, rhs: Displacement) {
+impl<const N: usize> AddAssign<Displacement<N>> for Scanner<N> {
+    fn add_assign(&mut self, rhs: Displacement<N>) {
         for beacon in &mut self.beacons {
             *beacon += rhs;
         }
     }
 }
 
-impl Scanner {
-    pub fn new(beacons: Vec<Point>) -> Self {
-        Self { beacons }
+impl<const N: usize> Scanner<N> {
+    pub fn new(id: usize, beacons: Vec<Point<N>>) -> Self {
+        Self { id, beacons }
     }
 
-    pub fn rotate(&mut self, rot: &Rotation) {
+    pub fn rotate(&mut self, rot: &Rotation<N>) {
         for beacon in &mut self.beacons {
             beacon.rotate(rot);
         }
     }
 
     /// return a list of distances from `center` to all points in `self`. distances are guaranteed to be in the same order as points in `self`.
-    pub fn dists(&self, center: &Point) -> Vec<u32> {
+    pub fn dists(&self, center: &Point<N>) -> Vec<u64> {
         self.beacons
             .iter()
             .map(|b| b.dist_squclid(center))
             .collect()
     }
 
-    /// take two scanners. return `Some(`the rotation & translation needed to turn `self` into `other``)` if they have at least `MIN_OVERLAP` points in common.
-    pub fn diff(&self, other: &Self) -> Option<(Rotation, Displacement)> {
-        // maps reference frame diffs to the number of pairs found for them.
-        let mut possible_diffs: HashMap<(Rotation, Displacement), u8> = HashMap::new();
+    /// take two scanners. return `Some(`the rotation & translation needed to turn `self` into `other``)` if they have at least `MIN_OVERLAP` points in common, and neither is missing any beacons within `RANGE` that the other sees.
+    pub fn diff(&self, other: &Self) -> Option<(Rotation<N>, Displacement<N>)> {
+        self.diff_with(other, &AlignConfig::default())
+    }
 
+    /// the same as `diff`, but with the overlap and range from `config`.
+    pub fn diff_with(
+        &self,
+        other: &Self,
+        config: &AlignConfig,
+    ) -> Option<(Rotation<N>, Displacement<N>)> {
+        // diffs which got enough votes but failed `verify`, so shouldn't be looked at again.
+        let mut rejected = HashSet::new();
+        self.vote(other, |rot, dsp, votes| {
+            if votes < config.min_overlap || rejected.contains(&(rot, dsp)) {
+                return false;
+            }
+            if config
+                .range
+                .is_none_or(|range| self.verify(other, &rot, dsp, range))
+            {
+                return true;
+            }
+            trace!("rejecting {rot} {dsp}: beacons missing");
+            rejected.insert((rot, dsp));
+            false
+        })
+    }
+
+    /// the most beacons that any one diff lines up between `self` and `other`, and that diff, whether or not it's enough to count as a match. the diff is `None` if there aren't even two beacons which line up, in which case the overlap is 1, or 0 if either scanner is empty.
+    pub fn best_overlap(&self, other: &Self) -> (usize, Option<(Rotation<N>, Displacement<N>)>) {
+        let mut best = (
+            usize::from(!self.beacons.is_empty() && !other.beacons.is_empty()),
+            None,
+        );
+        self.vote(other, |rot, dsp, votes| {
+            if votes > best.0 {
+                best = (votes, Some((rot, dsp)));
+            }
+            false
+        });
+        best
+    }
+
+    /// the guts of `diff`: for every diff which could turn `other` into `self`, count the beacons which line up. `on_vote` is called with the new count every time a diff gets another vote, and if it returns `true`, that diff is returned straight away.
+    fn vote(
+        &self,
+        other: &Self,
+        mut on_vote: impl FnMut(Rotation<N>, Displacement<N>, usize) -> bool,
+    ) -> Option<(Rotation<N>, Displacement<N>)> {
         // for each pair of points, assume that they are the same point. when they are, the diff will be found.
         for origin in &self.beacons {
             let dists = self.dists(origin);
             for target in &other.beacons {
                 trace!("if {origin} == {target}:");
+                // maps reference frame diffs to the number of pairs found for them, assuming `origin == target`.
+                let mut possible_diffs: HashMap<(Rotation<N>, Displacement<N>), usize> =
+                    HashMap::new();
+
                 // test distances from `target` to points in `other` to see if they match the distances from `origin` to points in `self`.
                 for aux in &other.beacons {
                     let dist = target.dist_squclid(aux);
@@ -337,33 +782,24 @@ impl Scanner {
                     }
 
                     for (i, _) in dists.iter().enumerate().filter(|&(_, &d)| d == dist) {
-                        // found a match! assuming that `aux` and `self[i]` are also the same point, find what the diff between `self` and `other` is, then increment that entry in `possible_diffs`.
-                        let rel_aux = Point::new(0, 0, 0) + (*aux - *target);
-                        let rel_ith = Point::new(0, 0, 0) + (self.beacons[i] - *origin);
-                        if rel_ith == Point::new(0, 0, 0) {
-                            continue;
-                        }
+                        // found a match! assuming that `aux` and `self[i]` are also the same point, find what the diff between `self` and `other` could be, then increment those entries in `possible_diffs`. if `aux - target` is symmetric in some way, there will be more than one possibility, but only the right one will keep getting votes.
+                        let rel_aux = Point::ORIGIN + (*aux - *target);
+                        let rel_ith = Point::ORIGIN + (self.beacons[i] - *origin);
+                        for rot in rel_aux.rots_between(rel_ith) {
+                            let dsp = *origin - rot.apply(*target);
+                            let entry = possible_diffs
+                                .entry((rot, dsp))
+                                .or_insert(1 /*for origin->target*/);
+                            *entry += 1;
 
-                        let rot = rel_aux.rot_diff(rel_ith);
-                        if !rot.is_valid() {
-                            continue;
-                        }
+                            trace!(
+                                "    {aux} == {} ({rel_aux} == {rel_ith}) with rot {rot}",
+                                self.beacons[i]
+                            );
 
-                        let mut target = *target;
-                        target.rotate(&rot);
-                        let dsp = *origin - target;
-                        let entry = possible_diffs
-                            .entry((rot, dsp))
-                            .or_insert(1 /*for origin->target*/);
-                        *entry += 1;
-
-                        trace!(
-                            "    {aux} == {} ({rel_aux} == {rel_ith}) with rot {rot}",
-                            self.beacons[i]
-                        );
-
-                        if *entry >= MIN_OVERLAP {
-                            return Some((rot, dsp));
+                            if on_vote(rot, dsp, *entry) {
+                                return Some((rot, dsp));
+                            }
                         }
                     }
                 }
@@ -373,12 +809,450 @@ impl Scanner {
         None
     }
 
-    /// extend `self` with `other`, assuming that they have already been converted to be in the same reference frame. avoids duplicates.
-    pub fn extend(&mut self, other: &mut Self) {
-        while let Some(beacon) = other.beacons.pop() {
-            if !self.beacons.contains(&beacon) {
-                self.beacons.push(beacon);
+    /// check a diff from `diff`: with `other` moved into `self`'s reference frame by `rot` and `dsp`, every beacon which either scanner sees within `range` of the other scanner must be seen by the other scanner too. a false match, where some beacons happen to line up by accident, will almost always fail this.
+    pub fn verify(
+        &self,
+        other: &Self,
+        rot: &Rotation<N>,
+        dsp: Displacement<N>,
+        range: Coord,
+    ) -> bool {
+        let origin = Point::ORIGIN;
+        let other_pos = origin + dsp;
+        // a point too far away for the difference to fit in a `Coord` is certainly out of range.
+        let in_range = |center: Point<N>, pt: &Point<N>| {
+            pt.checked_sub(center)
+                .is_some_and(|d| d.0.iter().all(|d| d.unsigned_abs() <= range.unsigned_abs()))
+        };
+
+        let mine = self.beacons.iter().copied().collect::<HashSet<_>>();
+        // and so is a beacon which can't even be moved into `self`'s frame.
+        let theirs = other
+            .beacons
+            .iter()
+            .filter_map(|&b| rot.apply(b).checked_add(dsp))
+            .collect::<HashSet<_>>();
+        theirs
+            .iter()
+            .filter(|b| in_range(origin, b))
+            .all(|b| mine.contains(b))
+            && mine
+                .iter()
+                .filter(|b| in_range(other_pos, b))
+                .all(|b| theirs.contains(b))
+    }
+
+    /// extend `self` with `other`, assuming that they have already been converted to be in the same reference frame. avoids duplicates, and leaves `other` as it is. to keep track of how often each beacon was seen, use a `BeaconMap` instead.
+    pub fn extend(&mut self, other: &Self) {
+        let mut map = self.beacons.iter().copied().collect::<BeaconMap<N>>();
+        map.extend(other.beacons.iter().copied());
+        self.beacons = map.into_beacons();
+    }
+}
+
+/// the id in a `--- scanner N ---` line, or `None` if `line` doesn't look like a header at all.
+fn parse_header(line: &str) -> Option<Result<usize>> {
+    if !line.starts_with("---") {
+        return None;
+    }
+    let id = line
+        .strip_prefix("--- scanner ")
+        .and_then(|s| s.strip_suffix(" ---"))
+        .with_context(|| format!("bad header `{line}`, expected `--- scanner N ---`"));
+    Some(id.and_then(|id| id.parse().with_context(|| format!("bad scanner id `{id}`"))))
+}
+
+/// read the scanners' reports. each starts with a `--- scanner N ---` line, followed by one `x,y,z` line per beacon, and ends at a blank line, the next header, or the end of the input. scanners are returned in the order they appear, and no two may have the same id.
+pub fn parse_input<R: BufRead>(lines: &mut Lines<R>) -> Result<Vec<Scanner>> {
+    parse_input_dims(lines)
+}
+
+/// `parse_input` for scanners in `N` dimensions, which have `N` comma separated coordinates on each beacon line.
+pub fn parse_input_dims<const N: usize, R: BufRead>(
+    lines: &mut Lines<R>,
+) -> Result<Vec<Scanner<N>>> {
+    let mut scanners: Vec<Scanner<N>> = Vec::new();
+    // whether beacon lines belong to the last scanner in `scanners`, i.e. there hasn't been a blank line since its header.
+    let mut open = false;
+    for (i, line) in lines.enumerate() {
+        let n = i + 1;
+        let line = line?;
+        let line = line.strip_suffix('\r').unwrap_or(&line);
+
+        if line.trim().is_empty() {
+            open = false;
+        } else if let Some(id) = parse_header(line) {
+            let id = id.with_context(|| format!("line {n}"))?;
+            if let Some(prev) = scanners.iter().position(|s| s.id == id) {
+                bail!(
+                    "line {n}: scanner {id} appears twice (first as scanner #{prev} in the input)"
+                );
             }
+            scanners.push(Scanner::new(id, Vec::new()));
+            open = true;
+        } else if open {
+            let beacon = line
+                .parse()
+                .with_context(|| format!("line {n}: bad beacon `{line}`"))?;
+            scanners
+                .last_mut()
+                .expect("open implies a scanner")
+                .beacons
+                .push(beacon);
+        } else {
+            bail!("line {n}: expected a `--- scanner N ---` header, found `{line}`");
+        }
+    }
+
+    Ok(scanners)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub(crate) fn example() -> Vec<Scanner> {
+        parse_input(&mut include_str!("../../input/19e.txt").as_bytes().lines()).unwrap()
+    }
+
+    fn parse(input: &str) -> Result<Vec<Scanner>> {
+        parse_input(&mut input.as_bytes().lines())
+    }
+
+    #[test]
+    fn parse_ok() {
+        let scanners = example();
+        assert_eq!(
+            scanners.iter().map(|s| s.id).collect::<Vec<_>>(),
+            [0, 1, 2, 3, 4]
+        );
+        assert_eq!(scanners[0].beacons.len(), 25);
+        assert_eq!(scanners[4].beacons[0], Point::new(727, 592, 562));
+
+        // ids are kept as they are, whatever order they come in. blank lines are optional, and there can be several.
+        let input = "--- scanner 7 ---\r\n1,2,3\r\n-4,5,-6\r\n--- scanner 2 ---\n0,0,0\n\n\n--- scanner 0 ---\n\n--- scanner 3 ---\n9,9,9";
+        let scanners = parse(input).unwrap();
+        assert_eq!(
+            scanners.iter().map(|s| s.id).collect::<Vec<_>>(),
+            [7, 2, 0, 3]
+        );
+        assert_eq!(
+            scanners[0].beacons,
+            [Point::new(1, 2, 3), Point::new(-4, 5, -6)]
+        );
+        assert_eq!(scanners[1].beacons, [Point::new(0, 0, 0)]);
+        assert!(scanners[2].beacons.is_empty());
+        assert_eq!(scanners[3].beacons, [Point::new(9, 9, 9)]);
+
+        assert!(parse("").unwrap().is_empty());
+        assert_eq!(
+            scanners[0].to_string(),
+            "--- scanner 7 ---\n1,2,3\n-4,5,-6\n"
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err = |input| format!("{:#}", parse(input).unwrap_err());
+        assert!(err("--- scanner 0 ---\n1,2,3\n1,2\n").starts_with("line 3: bad beacon `1,2`"));
+        assert!(err("--- scanner 0 ---\n1,2,3,4").starts_with("line 2:"));
+        assert!(err("--- scanner 0 ---\n1,x,3").starts_with("line 2:"));
+        assert!(err("--- scanner 0 ---\n1,2,3\n\n--- scanner 0 ---\n")
+            .starts_with("line 4: scanner 0 appears twice"));
+        assert!(err("1,2,3\n").starts_with("line 1: expected a `--- scanner N ---` header"));
+        // a blank line ends a scanner, so beacons after it need a new header.
+        assert!(err("--- scanner 0 ---\n1,2,3\n\n4,5,6").starts_with("line 4: expected a"));
+        assert!(err("--- scanner ---\n").starts_with("line 1: bad header"));
+        assert!(err("--- scanner -1 ---\n").starts_with("line 1: bad scanner id `-1`"));
+    }
+
+    #[test]
+    fn rotations() {
+        assert_eq!(Rotation::ALL[0], Rotation::IDENTITY);
+        for (i, a) in Rotation::ALL.iter().enumerate() {
+            assert_eq!(a.det(), 1);
+            assert!(!Rotation::ALL[..i].contains(a), "{a} appears twice");
+            assert_eq!(a.compose(&a.inverse()), Rotation::IDENTITY);
+            assert_eq!(a.inverse().compose(a), Rotation::IDENTITY);
+            for b in Rotation::ALL {
+                let ab = a.compose(&b);
+                assert!(Rotation::ALL.contains(&ab));
+                let pt = Point::new(1, -2, 3);
+                assert_eq!(ab.apply(pt), a.apply(b.apply(pt)));
+            }
+        }
+
+        assert!(Rotation::new([[1, 0, 0], [0, 1, 0], [0, 0, -1]]).is_none());
+        assert_eq!(
+            Rotation::new([[0, -1, 0], [1, 0, 0], [0, 0, 1]])
+                .unwrap()
+                .to_string(),
+            "-y+x+z"
+        );
+
+        // the 24 rotations of an asymmetric point are all different.
+        let pt = Point::new(1, 2, 3);
+        let images = Rotation::ALL
+            .map(|r| r.apply(pt))
+            .into_iter()
+            .collect::<HashSet<_>>();
+        assert_eq!(images.len(), 24);
+    }
+
+    #[test]
+    fn rots_between_edge_cases() {
+        // a zero coordinate's sign can't be seen, but only one choice of it is a proper rotation.
+        let a = Point::new(5, 7, 0);
+        let b = Point::new(0, 5, 7);
+        let rots = a.rots_between(b).collect::<Vec<_>>();
+        assert_eq!(rots.len(), 1);
+        assert_eq!(rots[0].apply(a), b);
+
+        // a point on an axis can be spun freely about it.
+        let a = Point::new(5, 0, 0);
+        let b = Point::new(0, 0, 5);
+        let rots = a.rots_between(b).collect::<Vec<_>>();
+        assert_eq!(rots.len(), 4);
+        assert!(rots.iter().all(|r| r.apply(a) == b));
+
+        // equal magnitudes: a point on a diagonal can be turned about it by thirds.
+        let a = Point::new(3, -3, 3);
+        let rots = a.rots_between(a).collect::<Vec<_>>();
+        assert_eq!(rots.len(), 3);
+        assert!(rots.contains(&Rotation::IDENTITY));
+
+        // and one that can't be done by any rotation, only a reflection.
+        assert_eq!(
+            Point::new(1, 2, 3)
+                .rots_between(Point::new(-1, 2, 3))
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn diff_example() {
+        let scanners = example();
+        let (rot, dsp) = scanners[0].diff(&scanners[1]).unwrap();
+        assert_eq!(dsp, Displacement::new(68, -1246, -43));
+        assert_eq!(
+            rot.apply(Point::new(686, 422, 578)) + dsp,
+            Point::new(-618, -824, -621)
+        );
+
+        // moving scanner 1 into scanner 0's frame and merging them leaves out the 12 they share.
+        let mut moved = scanners[1].clone();
+        moved.rotate(&rot);
+        moved += dsp;
+        let mut merged = scanners[0].clone();
+        merged.extend(&moved);
+        assert_eq!(merged.beacons.len(), 25 + moved.beacons.len() - 12);
+        assert_eq!(merged.beacons[..25], scanners[0].beacons);
+        assert_eq!(moved.beacons.len(), scanners[1].beacons.len());
+    }
+
+    #[test]
+    fn verify() {
+        let mut scanners = example();
+        let (rot, dsp) = scanners[0].diff(&scanners[1]).unwrap();
+        assert!(scanners[0].verify(&scanners[1], &rot, dsp, RANGE));
+        let local = |global: Point| {
+            rot.inverse()
+                .apply(Point::default() + (global - (Point::default() + dsp)))
+        };
+
+        // a beacon which only scanner 1 sees, but which is outside scanner 0's range, is fine.
+        scanners[1].beacons.push(local(Point::new(0, -1500, 0)));
+        assert_eq!(scanners[0].diff(&scanners[1]), Some((rot, dsp)));
+
+        // but one inside both ranges means the match must be wrong.
+        scanners[1].beacons.push(local(Point::new(0, -700, 0)));
+        assert!(!scanners[0].verify(&scanners[1], &rot, dsp, RANGE));
+        assert_eq!(scanners[0].diff(&scanners[1]), None);
+        assert_eq!(scanners[1].diff(&scanners[0]), None);
+        let unchecked = AlignConfig {
+            range: None,
+            ..AlignConfig::default()
+        };
+        assert_eq!(
+            scanners[0].diff_with(&scanners[1], &unchecked),
+            Some((rot, dsp))
+        );
+
+        // and with a bigger overlap needed, they don't match at all.
+        let strict = AlignConfig {
+            min_overlap: 13,
+            range: None,
+        };
+        assert_eq!(scanners[0].diff_with(&scanners[1], &strict), None);
+
+        // beacons at the far ends of `Coord` are out of range rather than overflowing.
+        let low = Scanner::new(0, vec![Point::new(Coord::MIN, 0, 0)]);
+        let high = Scanner::new(1, vec![Point::new(Coord::MAX, 0, 0)]);
+        let dsp = Displacement::new(10, 0, 0);
+        assert!(low.verify(&high, &Rotation::IDENTITY, dsp, RANGE));
+        assert!(high.verify(&low, &Rotation::IDENTITY, -dsp, RANGE));
+    }
+
+    #[test]
+    fn wide_coords() {
+        let far = Point::new(30000, -30000, 30000);
+        let near = Point::new(-30000, 30000, -30000);
+        assert_eq!(far.dist_taxicab(&near), 180000);
+        assert_eq!(far.dist_squclid(&near), 3 * 60000 * 60000);
+        assert_eq!(far.abs_squclid(), 3 * 30000 * 30000);
+        assert_eq!((near - far).dist_taxicab(), 180000);
+        for rot in Rotation::ALL {
+            assert_eq!(rot.inverse().apply(rot.apply(far)), far);
+        }
+
+        let max = Point::new(Coord::MAX, 0, Coord::MIN);
+        assert_eq!(max.checked_add(Displacement::new(1, 0, 0)), None);
+        assert_eq!(max.checked_add(Displacement::new(0, 0, -1)), None);
+        assert_eq!(max.checked_sub(Point::new(-1, 0, 0)), None);
+        assert_eq!(
+            max.checked_add(Displacement::new(-1, 5, 1)),
+            Some(Point::new(Coord::MAX - 1, 5, Coord::MIN + 1))
+        );
+        assert_eq!(max.dist_taxicab(&Point::default()), u64::from(u32::MAX));
+
+        // the example, but with scanner 0 far enough away that the displacement doesn't fit in an i16.
+        let mut scanners = example();
+        let shift = far - Point::default();
+        scanners[0] += shift;
+        let (rot, dsp) = scanners[0].diff(&scanners[1]).unwrap();
+        assert_eq!(dsp, Displacement::new(30068, -31246, 29957));
+        assert_eq!(
+            rot.apply(Point::new(686, 422, 578)) + dsp,
+            Point::new(-618, -824, -621) + shift
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "coordinate overflow")]
+    fn overflow_panics() {
+        let _ = Point::new(Coord::MAX, 0, 0) + Displacement::new(1, 0, 0);
+    }
+
+    #[test]
+    fn diff_symmetric() {
+        // a lattice of beacons with lots of zero and equal-magnitude coordinates, which the old swap/reflect guessing got wrong.
+        let mut beacons = Vec::new();
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in 0..=1 {
+                    beacons.push(Point::new(x * 100, y * 100, z * 100 + x * y * 10));
+                }
+            }
+        }
+        let base = Scanner::new(0, beacons.clone());
+        for rot in Rotation::ALL {
+            let dsp = Displacement::new(17, -5, 300);
+            let moved = Scanner::new(
+                1,
+                beacons
+                    .iter()
+                    .map(|&b| rot.inverse().apply(b + Displacement::new(-17, 5, -300)))
+                    .collect(),
+            );
+            let (found_rot, found_dsp) = base.diff(&moved).unwrap();
+            // every point must land back where it started.
+            for (&b, &m) in beacons.iter().zip(&moved.beacons) {
+                assert_eq!(found_rot.apply(m) + found_dsp, b, "{rot} {dsp}");
+            }
+        }
+    }
+
+    /// every orientation in `N` dimensions is distinct, the set is closed, and `rots_between` agrees with checking each of them.
+    fn check_rotations<const N: usize>(count: usize, pt: Point<N>) {
+        let all = Rotation::<N>::all().collect::<Vec<_>>();
+        assert_eq!(all.len(), count);
+        assert_eq!(all[0], Rotation::IDENTITY);
+        for (i, a) in all.iter().enumerate() {
+            assert!(!all[..i].contains(a), "{a} appears twice");
+            assert_eq!(Rotation::new(a.matrix()), Some(*a));
+            assert_eq!(a.to_string().parse::<Rotation<N>>().unwrap(), *a);
+            assert_eq!(a.compose(&a.inverse()), Rotation::IDENTITY);
+            for b in &all {
+                let ab = a.compose(b);
+                assert!(all.contains(&ab));
+                assert_eq!(ab.apply(pt), a.apply(b.apply(pt)));
+            }
+            let image = a.apply(pt);
+            let expected = all
+                .iter()
+                .filter(|r| r.apply(pt) == image)
+                .copied()
+                .collect::<Vec<_>>();
+            assert_eq!(pt.rots_between(image).collect::<Vec<_>>(), expected);
+        }
+    }
+
+    #[test]
+    fn other_dimensions() {
+        assert_eq!(Rotation::<3>::all().collect::<Vec<_>>(), Rotation::ALL);
+        check_rotations(24, Point::new(1, -2, 0));
+        // in 2d, reflections count too.
+        check_rotations(8, Point::from([3, -4]));
+        let flip = Rotation::new([[1, 0], [0, -1]]).unwrap();
+        assert_eq!(flip.det(), -1);
+        assert_eq!(flip.to_string(), "+x-y");
+        check_rotations(192, Point::from([1, 0, -3, 3]));
+        assert!(Rotation::<4>::all().all(|r| r.det() == 1));
+        assert_eq!(
+            "-w+x+y+z".parse::<Rotation<4>>().unwrap().matrix(),
+            [[0, 0, 0, -1], [1, 0, 0, 0], [0, 1, 0, 0], [0, 0, 1, 0]]
+        );
+        assert!("+x+y".parse::<Rotation<4>>().is_err());
+        assert!("+x+y+w".parse::<Rotation>().is_err());
+        check_rotations(2, Point::from([5]));
+
+        // the example, flattened onto the x-y plane by dropping z, and turned over.
+        let flat = |scanners: &[Scanner]| {
+            scanners
+                .iter()
+                .map(|s| {
+                    let beacons = s.beacons.iter().map(|b| {
+                        let [x, y, _] = b.coords();
+                        Point::from([x, y])
+                    });
+                    Scanner::<2>::new(s.id, beacons.collect())
+                })
+                .collect::<Vec<_>>()
+        };
+        let scanners = example();
+        let mut flipped = flat(&scanners);
+        let (rot, dsp) = flipped[0].diff(&flipped[1]).unwrap();
+        assert_eq!(rot.to_string(), "-x+y");
+        assert_eq!(dsp, Displacement::from([68, -1246]));
+        flipped[1].rotate(&flip);
+        assert_eq!(
+            flipped[0].diff(&flipped[1]),
+            Some((rot.compose(&flip.inverse()), dsp))
+        );
+
+        // and scanner 0 lifted into 4d, then seen from all sorts of orientations.
+        let base = Scanner::<4>::new(
+            0,
+            scanners[0]
+                .beacons
+                .iter()
+                .map(|b| {
+                    let [x, y, z] = b.coords();
+                    Point::from([x, y, z, (3 * x + 5 * y - z) % 500])
+                })
+                .collect(),
+        );
+        let dsp = Displacement::from([5, -17, 300, -40]);
+        for rot in Rotation::<4>::all().step_by(7) {
+            let moved = Scanner::new(
+                1,
+                base.beacons
+                    .iter()
+                    .map(|&b| rot.inverse().apply(b + -dsp))
+                    .collect(),
+            );
+            assert_eq!(base.diff(&moved), Some((rot, dsp)), "{rot}");
         }
     }
 }
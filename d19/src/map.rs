@@ -0,0 +1,247 @@
+//! a merged set of beacons, for building up a map of the world as scanners are placed.
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{Coord, Point, Pose, Scanner};
+
+/// every beacon seen so far, each once, with the number of times it was seen. beacons stay in the order they were first seen.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BeaconMap<const N: usize = 3> {
+    beacons: Vec<Point<N>>,
+    /// how many times each beacon in `beacons` has been seen.
+    counts: Vec<usize>,
+    /// where each beacon is in `beacons`.
+    index: HashMap<Point<N>, usize>,
+    /// every beacon's first coordinate and index, so that range queries only have to look at a slice of the map.
+    by_first: BTreeSet<(Coord, usize)>,
+}
+
+impl<const N: usize> FromIterator<Point<N>> for BeaconMap<N> {
+    fn from_iter<I: IntoIterator<Item = Point<N>>>(iter: I) -> Self {
+        let mut map = Self::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<const N: usize> Extend<Point<N>> for BeaconMap<N> {
+    fn extend<I: IntoIterator<Item = Point<N>>>(&mut self, iter: I) {
+        for beacon in iter {
+            self.observe(beacon);
+        }
+    }
+}
+
+fn first<const N: usize>(pt: &Point<N>) -> Coord {
+    pt.coords().first().copied().unwrap_or(0)
+}
+
+impl<const N: usize> BeaconMap<N> {
+    pub fn new() -> Self {
+        Self {
+            beacons: Vec::new(),
+            counts: Vec::new(),
+            index: HashMap::new(),
+            by_first: BTreeSet::new(),
+        }
+    }
+
+    /// record a sighting of `beacon`. returns `true` if it hadn't been seen before.
+    pub fn observe(&mut self, beacon: Point<N>) -> bool {
+        self.observe_n(beacon, 1)
+    }
+
+    fn observe_n(&mut self, beacon: Point<N>, count: usize) -> bool {
+        if let Some(&i) = self.index.get(&beacon) {
+            self.counts[i] += count;
+            return false;
+        }
+        let i = self.beacons.len();
+        self.beacons.push(beacon);
+        self.counts.push(count);
+        self.index.insert(beacon, i);
+        self.by_first.insert((first(&beacon), i));
+        true
+    }
+
+    /// record everything `scanner` sees, moved into the first scanner's reference frame by its `pose`. returns the number of beacons which are new.
+    pub fn add_scanner(&mut self, scanner: &Scanner<N>, pose: &Pose<N>) -> usize {
+        scanner
+            .beacons
+            .iter()
+            .filter(|&&b| self.observe(pose.apply(b)))
+            .count()
+    }
+
+    /// add every sighting in `other` to `self`, leaving `other` as it is. beacons new to `self` go on the end, in `other`'s order.
+    pub fn merge(&mut self, other: &Self) {
+        for (beacon, count) in other.iter() {
+            self.observe_n(beacon, count);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.beacons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.beacons.is_empty()
+    }
+
+    pub fn contains(&self, beacon: &Point<N>) -> bool {
+        self.index.contains_key(beacon)
+    }
+
+    /// how many times `beacon` has been seen, which is 0 if it isn't in the map.
+    pub fn observations(&self, beacon: &Point<N>) -> usize {
+        self.index.get(beacon).map_or(0, |&i| self.counts[i])
+    }
+
+    /// every beacon, in the order they were first seen.
+    pub fn beacons(&self) -> &[Point<N>] {
+        &self.beacons
+    }
+
+    pub fn into_beacons(self) -> Vec<Point<N>> {
+        self.beacons
+    }
+
+    /// every beacon and the number of times it was seen, in the order they were first seen.
+    pub fn iter(&self) -> impl Iterator<Item = (Point<N>, usize)> + '_ {
+        self.beacons
+            .iter()
+            .copied()
+            .zip(self.counts.iter().copied())
+    }
+
+    /// the beacons no more than `range` from `center` along every axis, i.e. the ones a scanner at `center` with that range would see, in the order they were first seen.
+    pub fn within(&self, center: Point<N>, range: u32) -> Vec<Point<N>> {
+        let (lo, hi) = (
+            first(&center).saturating_sub_unsigned(range),
+            first(&center).saturating_add_unsigned(range),
+        );
+        let mut found = self
+            .by_first
+            .range((lo, 0)..=(hi, usize::MAX))
+            .map(|&(_, i)| i)
+            .filter(|&i| {
+                let (b, c) = (self.beacons[i].coords(), center.coords());
+                (0..N).all(|axis| b[axis].abs_diff(c[axis]) <= range)
+            })
+            .collect::<Vec<_>>();
+        found.sort_unstable();
+        found.into_iter().map(|i| self.beacons[i]).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        align,
+        gen::{World, WorldConfig},
+    };
+
+    #[test]
+    fn observe() {
+        let mut map = BeaconMap::new();
+        assert!(map.is_empty());
+        assert!(map.observe(Point::new(1, 2, 3)));
+        assert!(map.observe(Point::new(-1, 2, 3)));
+        assert!(!map.observe(Point::new(1, 2, 3)));
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.observations(&Point::new(1, 2, 3)), 2);
+        assert_eq!(map.observations(&Point::new(-1, 2, 3)), 1);
+        assert_eq!(map.observations(&Point::new(0, 0, 0)), 0);
+        assert!(!map.contains(&Point::new(0, 0, 0)));
+        assert_eq!(map.beacons(), [Point::new(1, 2, 3), Point::new(-1, 2, 3)]);
+
+        let collected = [
+            Point::new(1, 2, 3),
+            Point::new(-1, 2, 3),
+            Point::new(1, 2, 3),
+        ]
+        .into_iter()
+        .collect::<BeaconMap>();
+        assert_eq!(collected, map);
+    }
+
+    #[test]
+    fn merge() {
+        let scanners = crate::tests::example();
+        let alignment = align(&scanners);
+        let maps = scanners
+            .iter()
+            .zip(&alignment.poses)
+            .map(|(scanner, pose)| {
+                let mut map = BeaconMap::new();
+                assert_eq!(
+                    map.add_scanner(scanner, &pose.unwrap()),
+                    scanner.beacons.len()
+                );
+                map
+            })
+            .collect::<Vec<_>>();
+
+        let mut merged = BeaconMap::new();
+        for map in &maps {
+            merged.merge(map);
+        }
+        // nothing was taken from the maps being merged in.
+        assert_eq!(maps[0].len(), 25);
+        assert_eq!(merged.beacons(), alignment.beacons);
+        let sightings = merged.iter().map(|(_, n)| n).sum::<usize>();
+        assert_eq!(
+            sightings,
+            scanners.iter().map(|s| s.beacons.len()).sum::<usize>()
+        );
+        // scanners 0 and 1 share 12 beacons, and each of those was seen at least twice.
+        assert!(maps[0]
+            .beacons()
+            .iter()
+            .filter(|b| maps[1].contains(b))
+            .all(|b| merged.observations(b) >= 2));
+
+        let mut twice = merged.clone();
+        twice.merge(&merged);
+        assert_eq!(twice.len(), merged.len());
+        assert_eq!(
+            twice.observations(&merged.beacons()[0]),
+            2 * merged.iter().next().unwrap().1
+        );
+    }
+
+    #[test]
+    fn within() {
+        let world: World = World::generate(&WorldConfig {
+            scanners: 10,
+            ..WorldConfig::default()
+        })
+        .unwrap();
+        let map = world.beacons.iter().copied().collect::<BeaconMap>();
+        for (pose, scanner) in world.poses.iter().zip(&world.scanners) {
+            // exactly what each scanner saw.
+            let seen = map.within(pose.position, 1000);
+            assert_eq!(seen.len(), scanner.beacons.len());
+            for range in [0, 1, 250, 999, 5000] {
+                let expected = world
+                    .beacons
+                    .iter()
+                    .filter(|b| {
+                        let d = (**b - pose.position).coords();
+                        d.iter().all(|n| n.unsigned_abs() <= range)
+                    })
+                    .copied()
+                    .collect::<Vec<_>>();
+                assert_eq!(map.within(pose.position, range), expected);
+            }
+        }
+        assert!(BeaconMap::<3>::new()
+            .within(Point::default(), 10)
+            .is_empty());
+        // a range reaching past the edges of `Coord` doesn't overflow.
+        let edge = BeaconMap::from_iter([Point::new(Coord::MAX, 0, 0)]);
+        assert_eq!(edge.within(Point::new(Coord::MAX - 1, 0, 0), 5).len(), 1);
+        assert_eq!(edge.within(Point::new(Coord::MIN, 0, 0), u32::MAX).len(), 1);
+    }
+}
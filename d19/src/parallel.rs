@@ -0,0 +1,243 @@
+//! matching every pair of scanners at once on several threads, then working out the poses from all of the matches.
+
+use std::{
+    collections::VecDeque,
+    num::NonZeroUsize,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use log::trace;
+
+use crate::{
+    align::{merge_beacons, Match},
+    AlignConfig, Alignment, Fingerprint, Pose, Scanner,
+};
+
+/// the number of scanners below which `align` is usually faster than `align_parallel`, since it can stop as soon as everything is placed rather than trying every pair.
+pub const MIN_PARALLEL_SCANNERS: usize = 40;
+
+/// run `diff_with` on every pair of scanners whose fingerprints allow it, sharing the pairs out between `threads` threads. the matches come back sorted by `(base, other)`, with `base < other`, whichever thread found them.
+pub fn match_pairs<const N: usize>(
+    scanners: &[Scanner<N>],
+    config: &AlignConfig,
+    threads: NonZeroUsize,
+) -> Vec<Match<N>> {
+    let fingerprints = scanners.iter().map(Fingerprint::new).collect::<Vec<_>>();
+    let mut pairs = Vec::new();
+    for base in 0..scanners.len() {
+        for other in base + 1..scanners.len() {
+            if fingerprints[base].plausible(&fingerprints[other], config.min_overlap) {
+                pairs.push((base, other));
+            }
+        }
+    }
+    trace!("{} plausible pairs", pairs.len());
+
+    // each thread takes the next pair off the list until there are none left.
+    let next = AtomicUsize::new(0);
+    let mut found = vec![None; pairs.len()];
+    thread::scope(|s| {
+        let handles = (0..threads.get().min(pairs.len()))
+            .map(|_| {
+                s.spawn(|| {
+                    let mut res = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(&(base, other)) = pairs.get(i) else {
+                            break;
+                        };
+                        if let Some((rotation, displacement)) =
+                            scanners[base].diff_with(&scanners[other], config)
+                        {
+                            res.push((
+                                i,
+                                Match {
+                                    base,
+                                    other,
+                                    rotation,
+                                    displacement,
+                                },
+                            ));
+                        }
+                    }
+                    res
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for handle in handles {
+            for (i, m) in handle.join().expect("matching thread panicked") {
+                found[i] = Some(m);
+            }
+        }
+    });
+
+    found.into_iter().flatten().collect()
+}
+
+/// work out every scanner's pose from a list of matches, by a breadth-first search out from the first scanner. a scanner's neighbors are visited in order of index, so the same matches always give the same poses and the same tree of matches, whatever order they're given in.
+pub fn solve<const N: usize>(
+    count: usize,
+    matches: &[Match<N>],
+) -> (Vec<Option<Pose<N>>>, Vec<Match<N>>) {
+    let mut poses = vec![None; count];
+    let mut tree = Vec::new();
+    if count == 0 {
+        return (poses, tree);
+    }
+
+    // matches work in both directions.
+    let mut edges = vec![Vec::new(); count];
+    for m in matches {
+        edges[m.base].push(*m);
+        edges[m.other].push(m.inverse());
+    }
+    for edges in &mut edges {
+        edges.sort_unstable_by_key(|m| m.other);
+    }
+
+    poses[0] = Some(Pose::ORIGIN);
+    let mut queue = VecDeque::from([0]);
+    while let Some(base) = queue.pop_front() {
+        let pose = poses[base].expect("queued scanners are placed");
+        for m in &edges[base] {
+            if poses[m.other].is_none() {
+                poses[m.other] = Some(pose.then(&m.rotation, m.displacement));
+                tree.push(*m);
+                queue.push_back(m.other);
+            }
+        }
+    }
+
+    (poses, tree)
+}
+
+/// the same as `align_with`, but with the matching done by `match_pairs` on `threads` threads. this tries every plausible pair rather than stopping when everything is placed, but the poses and beacons are the same.
+pub fn align_parallel<const N: usize>(
+    scanners: &[Scanner<N>],
+    config: &AlignConfig,
+    threads: NonZeroUsize,
+) -> Alignment<N> {
+    let matches = match_pairs(scanners, config, threads);
+    let (poses, matches) = solve(scanners.len(), &matches);
+    let beacons = merge_beacons(scanners, &poses);
+    Alignment {
+        poses,
+        beacons,
+        matches,
+    }
+}
+
+/// `align` for small inputs or when there's only one thread to work with, and `align_parallel` on every available thread for big ones.
+pub fn align_auto<const N: usize>(scanners: &[Scanner<N>]) -> Alignment<N> {
+    let threads = (scanners.len() >= MIN_PARALLEL_SCANNERS)
+        .then(|| thread::available_parallelism().ok())
+        .flatten()
+        .filter(|threads| threads.get() > 1);
+    match threads {
+        Some(threads) => align_parallel(scanners, &AlignConfig::default(), threads),
+        None => crate::align(scanners),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::{
+        align_with,
+        gen::{World, WorldConfig},
+    };
+
+    fn threads(n: usize) -> NonZeroUsize {
+        NonZeroUsize::new(n).unwrap()
+    }
+
+    #[test]
+    fn example() {
+        let scanners = crate::tests::example();
+        let serial = crate::align(&scanners);
+        for n in [1, 2, 3, 8] {
+            let parallel = align_parallel(&scanners, &AlignConfig::default(), threads(n));
+            assert_eq!(parallel.poses, serial.poses);
+            assert_eq!(parallel.beacons.len(), 79);
+            assert_eq!(parallel.max_distance(), 3621);
+            assert_eq!(parallel.matches.len(), 4);
+        }
+        assert_eq!(align_auto(&scanners).poses, serial.poses);
+    }
+
+    #[test]
+    fn generated() {
+        let config = WorldConfig {
+            seed: 38,
+            scanners: 20,
+            ..WorldConfig::default()
+        };
+        let world: World = World::generate(&config).unwrap();
+        let align_config = AlignConfig::default();
+        let serial = align_with(&world.scanners, &align_config);
+        let first = align_parallel(&world.scanners, &align_config, threads(1));
+        for n in [2, 5] {
+            let parallel = align_parallel(&world.scanners, &align_config, threads(n));
+            assert_eq!(parallel.poses, first.poses);
+            assert_eq!(parallel.beacons, first.beacons);
+            assert_eq!(parallel.matches, first.matches);
+        }
+        assert_eq!(first.poses, serial.poses);
+        assert_eq!(
+            first.poses,
+            world.poses.iter().copied().map(Some).collect::<Vec<_>>()
+        );
+        assert_eq!(first.beacons.len(), world.beacons.len());
+    }
+
+    #[test]
+    fn solve_order() {
+        // the same matches in any order, and either way around, give the same answer.
+        let scanners = crate::tests::example();
+        let matches = match_pairs(&scanners, &AlignConfig::default(), threads(2));
+        let (poses, tree) = solve(scanners.len(), &matches);
+
+        let mut shuffled = matches.iter().map(Match::inverse).collect::<Vec<_>>();
+        shuffled.reverse();
+        let (shuffled_poses, shuffled_tree) = solve(scanners.len(), &shuffled);
+        assert_eq!(poses, shuffled_poses);
+        assert_eq!(tree, shuffled_tree);
+
+        // every placed scanner but 0 is reached by exactly one match in the tree.
+        let reached = tree.iter().map(|m| m.other).collect::<HashSet<_>>();
+        assert_eq!(reached.len(), tree.len());
+        assert!(!reached.contains(&0));
+
+        // a scanner with no matches isn't placed.
+        let (poses, _) = solve(scanners.len() + 1, &matches);
+        assert_eq!(poses[scanners.len()], None);
+        assert_eq!(solve::<3>(0, &[]).0, vec![]);
+    }
+
+    #[test]
+    fn inverse() {
+        let scanners = crate::tests::example();
+        let (rotation, displacement) = scanners[0].diff(&scanners[1]).unwrap();
+        let m = Match {
+            base: 0,
+            other: 1,
+            rotation,
+            displacement,
+        };
+        let (rotation, displacement) = scanners[1].diff(&scanners[0]).unwrap();
+        assert_eq!(
+            m.inverse(),
+            Match {
+                base: 1,
+                other: 0,
+                rotation,
+                displacement,
+            }
+        );
+        assert_eq!(m.inverse().inverse(), m);
+    }
+}
@@ -1,13 +1,16 @@
 use anyhow::{Result, Context};
 
+use d20::PackedImage;
+
 fn main() -> Result<()> {
     simple_logger::init_with_env().context("couldn't initialize logger")?;
 
-    let (alg, mut img) = d20::parse_input()?;
+    let (alg, img) = d20::parse_input()?;
+    let mut img = PackedImage::from(&img);
     for _ in 0..2 {
-        img = img.enhance(&alg);
+        img = img.enhance_auto(&alg);
     }
-    println!("{}", img.count());
+    println!("{}", img.count().context("infinitely many pixels are lit")?);
     Ok(())
 }
 
@@ -18,14 +21,21 @@ mod tests {
     #[test]
     fn neighbors() {
         let point = Point::new(5, 10);
-        for neighbor in point.neighbors() {
-            println!("{neighbor}");
-        }
+        let neighbors = point.neighbors().map(|p| p.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            neighbors,
+            ["4,9", "5,9", "6,9", "4,10", "5,10", "6,10", "4,11", "5,11", "6,11"]
+        );
     }
 
     #[test]
     fn get_enhanced_px() {
-        let (alg, img) = parse_input().unwrap();
-        println!("{}", img.get_enhanced_px(Point::new(2, 2), &alg));
+        let (alg, img) =
+            parse_input_from(include_str!("../../../input/20e.txt").as_bytes()).unwrap();
+        // the middle pixel of the example has index 34, which lights it up.
+        assert!(alg[34]);
+        assert!(img.get_enhanced_px(Point::new(2, 2), &alg));
+        assert!(!alg[0]);
+        assert!(!img.get_enhanced_px(Point::new(-5, -5), &alg));
     }
 }
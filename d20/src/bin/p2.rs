@@ -1,12 +1,15 @@
 use anyhow::{Result, Context};
 
+use d20::PackedImage;
+
 fn main() -> Result<()> {
     simple_logger::init_with_env().context("couldn't initialize logger")?;
 
-    let (alg, mut img) = d20::parse_input()?;
+    let (alg, img) = d20::parse_input()?;
+    let mut img = PackedImage::from(&img);
     for _ in 0..50 {
-        img = img.enhance(&alg);
+        img = img.enhance_auto(&alg);
     }
-    println!("{}", img.count());
+    println!("{}", img.count().context("infinitely many pixels are lit")?);
     Ok(())
 }
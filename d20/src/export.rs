@@ -0,0 +1,411 @@
+//! reading and writing images in formats other tools understand: PBM bitmaps, and golly's RLE patterns.
+//!
+//! neither format knows about the infinite background around an image, so both get a comment like `d20 origin=2,-1 background=#` saying where the image is and what's around it. files without one are read as sitting at the origin on a dark background.
+
+use std::{
+    fmt::Display,
+    io::{self, BufRead, Write},
+};
+
+use anyhow::{bail, Context, Result};
+
+use common::netpbm::{self, Data, Encoding, ToNetpbm};
+
+use crate::{Algorithm, Image, Point};
+
+/// the most pixels a whole bitmap can have: one read from an RLE pattern, whose header says how big it is before there's any data to back that up, or one drawn for a PBM.
+pub(crate) const MAX_BITMAP_PIXELS: usize = 1 << 28;
+
+/// what a file needs to say about an image apart from its pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Header {
+    origin: Point,
+    background: bool,
+}
+
+impl Default for Header {
+    fn default() -> Self {
+        Self {
+            origin: Point::new(0, 0),
+            background: false,
+        }
+    }
+}
+
+impl Header {
+    pub(crate) fn of(image: &Image) -> Self {
+        Self {
+            origin: image.origin(),
+            background: image.background(),
+        }
+    }
+
+    /// the header in a comment, or `None` if the comment isn't one.
+    fn parse(comment: &str) -> Result<Option<Self>> {
+        let Some(fields) = comment.trim().strip_prefix("d20 ") else {
+            return Ok(None);
+        };
+        let mut header = Self::default();
+        for field in fields.split_whitespace() {
+            match field.split_once('=') {
+                Some(("origin", origin)) => {
+                    let (x, y) = origin
+                        .split_once(',')
+                        .with_context(|| format!("bad origin `{origin}`, expected `x,y`"))?;
+                    header.origin = Point::new(x.parse()?, y.parse()?);
+                }
+                Some(("background", "#")) => header.background = true,
+                Some(("background", ".")) => header.background = false,
+                _ => bail!("bad header field `{field}`"),
+            }
+        }
+        Ok(Some(header))
+    }
+
+    /// the image made of `pixels`, `width` to a row, with the first one at `origin`, or an error if some of it would be out of range of a `Point`.
+    fn image(&self, width: usize, pixels: &[bool]) -> Result<Image> {
+        let height = pixels.len().div_ceil(width);
+        let fits = |origin: i32, len: usize| {
+            i32::try_from(len)
+                .ok()
+                .and_then(|len| origin.checked_add(len))
+                .is_some()
+        };
+        if !fits(self.origin.x, width) || !fits(self.origin.y, height) {
+            bail!("a {width}x{height} image at {} doesn't fit", self.origin);
+        }
+
+        let mut exceptions = Vec::new();
+        for (i, &px) in pixels.iter().enumerate() {
+            if px != self.background {
+                // both fit, as checked above.
+                exceptions.push(Point::new(
+                    self.origin.x + (i % width) as i32,
+                    self.origin.y + (i / width) as i32,
+                ));
+            }
+        }
+        Ok(Image::from_pixels(exceptions, self.background))
+    }
+}
+
+impl Display for Header {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let background = if self.background { '#' } else { '.' };
+        write!(f, "d20 origin={} background={background}", self.origin)
+    }
+}
+
+/// the header from the first of `comments` which is one, if any are.
+fn find_header<'a>(comments: impl IntoIterator<Item = &'a str>) -> Result<Option<Header>> {
+    for comment in comments {
+        if let Some(header) = Header::parse(comment)? {
+            return Ok(Some(header));
+        }
+    }
+    Ok(None)
+}
+
+impl Image {
+    /// read a bitmap, which has to be a PBM.
+    pub fn from_netpbm(img: &netpbm::Image) -> Result<Self> {
+        let Data::Bitmap(pixels) = &img.data else {
+            bail!("expected a bitmap, found a .{}", img.extension());
+        };
+        let header = find_header(img.comments.iter().map(String::as_str))?.unwrap_or_default();
+        header.image(img.width.max(1), pixels)
+    }
+
+    /// read a PBM file, in either encoding.
+    pub fn read_pbm<R: BufRead>(r: R) -> Result<Self> {
+        Self::from_netpbm(&netpbm::Image::read(r)?)
+    }
+
+    pub fn write_pbm<W: Write>(&self, w: W, encoding: Encoding) -> Result<()> {
+        Ok(self.to_netpbm()?.write(w, encoding)?)
+    }
+
+    /// read a golly RLE pattern, and its rule if it has one. both `b`/`o` and `.`/`A` work for dark and lit pixels, and `#CXRLE Pos=x,y` is understood if there's no header of our own.
+    pub fn read_rle<R: BufRead>(r: R) -> Result<(Self, Option<Algorithm>)> {
+        let mut lines = r.lines().enumerate();
+        let mut comments = Vec::new();
+        let mut size = None;
+        let mut rule = None;
+        for (n, line) in lines.by_ref() {
+            let line = line?;
+            if let Some(comment) = line.strip_prefix('#') {
+                comments.push(comment.to_owned());
+                continue;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let (mut x, mut y) = (None, None);
+            for field in line.split(',') {
+                let (key, value) = field
+                    .split_once('=')
+                    .with_context(|| format!("line {}: bad field `{field}`", n + 1))?;
+                let value = value.trim();
+                match key.trim() {
+                    "x" => x = Some(value.parse::<usize>()?),
+                    "y" => y = Some(value.parse::<usize>()?),
+                    "rule" => rule = Some(Algorithm::from_rule(value)?),
+                    key => bail!("line {}: unknown field `{key}`", n + 1),
+                }
+            }
+            size = x.zip(y);
+            if size.is_none() {
+                bail!("line {}: expected `x = ..., y = ...`", n + 1);
+            }
+            break;
+        }
+        let (width, height) = size.context("missing `x = ..., y = ...` line")?;
+
+        let header = match find_header(
+            comments
+                .iter()
+                .filter_map(|c| c.strip_prefix(['C', 'c']).map(str::trim)),
+        )? {
+            Some(header) => header,
+            None => {
+                let pos = comments
+                    .iter()
+                    .filter_map(|c| c.strip_prefix("CXRLE"))
+                    .flat_map(str::split_whitespace)
+                    .find_map(|field| field.strip_prefix("Pos="));
+                let mut header = Header::default();
+                if let Some(pos) = pos {
+                    let (x, y) = pos
+                        .split_once(',')
+                        .with_context(|| format!("bad position `{pos}`"))?;
+                    header.origin = Point::new(x.parse()?, y.parse()?);
+                }
+                header
+            }
+        };
+
+        let area = width
+            .checked_mul(height)
+            .filter(|&area| area <= MAX_BITMAP_PIXELS)
+            .with_context(|| format!("{width}x{height} is too big"))?;
+        // anything the pattern doesn't mention is dark, whatever the background.
+        let mut pixels = vec![false; area];
+        let (mut x, mut y) = (0_usize, 0_usize);
+        let mut run = None;
+        'body: for (n, line) in lines {
+            for (col, c) in line?.chars().enumerate() {
+                let at = || format!("line {}, column {}", n + 1, col + 1);
+                if let Some(digit) = c.to_digit(10) {
+                    run = Some(
+                        run.unwrap_or(0_usize)
+                            .checked_mul(10)
+                            .and_then(|run| run.checked_add(digit as usize))
+                            .with_context(|| format!("{}: run is too long", at()))?,
+                    );
+                    continue;
+                }
+                let count = run.take().unwrap_or(1);
+                match c {
+                    'b' | '.' | 'o' | 'A' => {
+                        if x.checked_add(count).is_none_or(|end| end > width) || y >= height {
+                            bail!("{}: pattern is bigger than {width}x{height}", at());
+                        }
+                        for px in &mut pixels[y * width + x..][..count] {
+                            *px = matches!(c, 'o' | 'A');
+                        }
+                        x += count;
+                    }
+                    // running off the bottom is only a problem if something is drawn there.
+                    '$' => (x, y) = (0, y.saturating_add(count)),
+                    '!' => break 'body,
+                    c if c.is_whitespace() => (),
+                    _ => bail!("{}: unexpected `{c}`", at()),
+                }
+            }
+        }
+        Ok((header.image(width.max(1), &pixels)?, rule))
+    }
+
+    /// write a golly RLE pattern of the bounding box, and `rule` as a MAP string if there is one.
+    pub fn write_rle<W: Write>(&self, mut w: W, rule: Option<&Algorithm>) -> io::Result<()> {
+        writeln!(w, "#C {}", Header::of(self))?;
+        let (width, height) = self.dimensions();
+        write!(w, "x = {width}, y = {height}")?;
+        if let Some(rule) = rule {
+            write!(w, ", rule = {}", rule.to_map())?;
+        }
+        writeln!(w)?;
+
+        let mut tokens = Vec::new();
+        let token = |count: usize, c: char| match count {
+            1 => c.to_string(),
+            _ => format!("{count}{c}"),
+        };
+        let mut rows_ended = 0;
+        for runs in self.row_runs() {
+            // dark pixels at the end of a row, and empty rows at the end, go without saying.
+            let len = runs.iter().rposition(|&(px, _)| px).map_or(0, |i| i + 1);
+            if len > 0 && rows_ended > 0 {
+                tokens.push(token(rows_ended, '$'));
+                rows_ended = 0;
+            }
+            for &(px, run) in &runs[..len] {
+                tokens.push(token(run, if px { 'o' } else { 'b' }));
+            }
+            rows_ended += 1;
+        }
+        tokens.push("!".to_owned());
+
+        // lines shouldn't be longer than 70 characters.
+        let mut line = String::new();
+        for token in tokens {
+            if line.len() + token.len() > 70 {
+                writeln!(w, "{line}")?;
+                line.clear();
+            }
+            line.push_str(&token);
+        }
+        writeln!(w, "{line}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::rng::Rng;
+
+    use super::*;
+    use crate::parse_input_from;
+
+    /// images with lit and dark backgrounds, stored densely and sparsely, a long way from the origin, and empty.
+    fn images() -> Vec<Image> {
+        let (alg, img) = parse_input_from(include_str!("../../input/20e.txt").as_bytes()).unwrap();
+        let mut rng = Rng::new(50);
+        let noise = (0..30)
+            .map(|_| (0..100).map(|_| rng.chance(0.3)).collect())
+            .collect::<Vec<_>>();
+        let flip = Algorithm::from_fn(|i| i & 0b000_010_000 == 0);
+        vec![
+            img.clone(),
+            img.enhance(&alg).enhance(&alg),
+            img.enhance(&flip),
+            Image::from_rows(&noise).enhance(&flip),
+            Image::from_pixels(vec![Point::new(-500, 20), Point::new(300, -7)], false),
+            Image::default(),
+            Image::default().enhance(&flip),
+        ]
+    }
+
+    #[test]
+    fn pbm() {
+        for img in images() {
+            for encoding in [Encoding::Plain, Encoding::Raw] {
+                let mut bytes = Vec::new();
+                img.write_pbm(&mut bytes, encoding).unwrap();
+                assert_eq!(Image::read_pbm(&bytes[..]).unwrap(), img);
+            }
+        }
+
+        // anything else reads as being at the origin on a dark background.
+        let img = Image::read_pbm(&b"P1\n3 2\n0 1 0\n0 0 1\n"[..]).unwrap();
+        assert_eq!(img.to_string(), "#.\n.#\n");
+        assert_eq!(img.origin(), Point::new(1, 0));
+        assert!(!img.background());
+
+        let img = Image::read_pbm(&b"P1\n# d20 origin=-5,3 background=#\n2 1\n0 1\n"[..]).unwrap();
+        assert_eq!(img.count(), None);
+        assert!(!img.get(Point::new(-5, 3)));
+        assert!(img.get(Point::new(-4, 3)));
+        assert!(img.get(Point::new(0, 0)));
+
+        assert!(Image::read_pbm(&b"P2\n1 1\n255\n0\n"[..]).is_err());
+        assert!(Image::read_pbm(&b"P1\n# d20 origin=5 background=#\n1 1\n0\n"[..]).is_err());
+    }
+
+    #[test]
+    fn rle() {
+        for img in images() {
+            for rule in [None, Some(Algorithm::life())] {
+                let mut bytes = Vec::new();
+                img.write_rle(&mut bytes, rule.as_ref()).unwrap();
+                let text = String::from_utf8(bytes).unwrap();
+                assert!(text
+                    .lines()
+                    .all(|line| line.len() <= 70 || line.starts_with('x')));
+                assert_eq!(
+                    Image::read_rle(text.as_bytes()).unwrap(),
+                    (img.clone(), rule)
+                );
+            }
+        }
+
+        let glider = "#N Glider\n#CXRLE Pos=-1,-1\nx = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n";
+        let (img, rule) = Image::read_rle(glider.as_bytes()).unwrap();
+        assert_eq!(rule, Some(Algorithm::life()));
+        assert_eq!(img.to_string(), ".#.\n..#\n###\n");
+        assert_eq!(img.origin(), Point::new(-1, -1));
+        let mut written = Vec::new();
+        img.write_rle(&mut written, None).unwrap();
+        assert_eq!(
+            String::from_utf8(written).unwrap(),
+            "#C d20 origin=-1,-1 background=.\nx = 3, y = 3\nbo$2bo$3o!\n"
+        );
+
+        // a sparse image is written from its runs, however big its box.
+        let far = Image::from_pixels(vec![Point::new(0, 0), Point::new(2_000_000_000, 1)], false);
+        let mut written = Vec::new();
+        far.write_rle(&mut written, None).unwrap();
+        let text = String::from_utf8(written).unwrap();
+        assert_eq!(
+            text.lines().skip(1).collect::<Vec<_>>(),
+            ["x = 2000000001, y = 2", "o$2000000000bo!"]
+        );
+
+        // multi-state style, with gaps between rows and a line break in the middle of a run.
+        let (img, rule) = Image::read_rle(&b"x = 12, y = 3\n.A$$1\n0A.A!"[..]).unwrap();
+        assert_eq!(rule, None);
+        assert_eq!(
+            img.to_string(),
+            ".#..........\n............\n##########.#\n"
+        );
+        assert_eq!(img.origin(), Point::new(0, 0));
+
+        for bad in [
+            "bo$2bo$3o!",
+            "x = 3\nbo!",
+            "x = 2, y = 1\n3o!",
+            "x = 2, y = 1\no$o!",
+            "x = 2, y = 1\nox!",
+            "x = 2, y = 1, rule = B9/S\no!",
+        ] {
+            assert!(Image::read_rle(bad.as_bytes()).is_err(), "{bad}");
+        }
+
+        // hostile headers and runs are errors rather than panics or huge allocations.
+        let err = |rle: &str| Image::read_rle(rle.as_bytes()).unwrap_err().to_string();
+        assert_eq!(
+            err("x = 18446744073709551615, y = 2\no!"),
+            "18446744073709551615x2 is too big"
+        );
+        assert_eq!(
+            err("x = 100000, y = 100000\no!"),
+            "100000x100000 is too big"
+        );
+        assert_eq!(
+            err("x = 2, y = 1\n99999999999999999999999o!"),
+            "line 2, column 20: run is too long"
+        );
+        assert_eq!(
+            err("x = 2, y = 1\no18446744073709551615o!"),
+            "line 2, column 22: pattern is bigger than 2x1"
+        );
+        assert!(err("x = 2, y = 1\n18446744073709551615$o!").contains("bigger than 2x1"));
+        assert!(Image::read_rle(&b"x = 2, y = 1\no18446744073709551615$!"[..]).is_ok());
+        assert!(
+            err("#C d20 origin=2147483647,0 background=.\nx = 2, y = 1\no!")
+                .contains("doesn't fit")
+        );
+        assert!(err("#CXRLE Pos=0,2147483647\nx = 1, y = 3\no!").contains("doesn't fit"));
+        assert!(err("x = 3000000000, y = 0\n!").contains("doesn't fit"));
+    }
+}
@@ -1,35 +1,159 @@
-use std::{convert::TryFrom, fmt::Display, io};
+pub mod export;
+pub mod packed;
+pub mod rule;
+
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    io::{self, BufRead},
+    iter,
+    ops::Index,
+    str::FromStr,
+};
 
 use derive_more::Display;
 
 use anyhow::{bail, Context, Result};
 
-pub fn parse_input() -> Result<([bool; 512], Image)> {
-    let mut lines = io::stdin().lines();
-    let alg_str = lines.next().context("missing input")??;
-    let mut alg = [false; 512];
-    for (i, c) in alg_str.chars().enumerate() {
-        alg[i] = c == '#';
+use common::{
+    neighbors::{Neighborhood, Order, Shape},
+    netpbm::{self, ToNetpbm},
+};
+
+pub use packed::PackedImage;
+
+pub fn parse_input() -> Result<(Algorithm, Image)> {
+    parse_input_from(io::stdin().lock())
+}
+
+/// `#` for a lit pixel and `.` for a dark one.
+fn parse_pixel(c: char) -> Option<bool> {
+    match c {
+        '#' => Some(true),
+        '.' => Some(false),
+        _ => None,
     }
+}
 
-    if !lines.next().context("missing input")??.is_empty() {
-        bail!("invalid input");
+/// read the algorithm, a blank line, and then the image, stopping at the end of the input or at another blank line. every row of the image must be the same length.
+pub fn parse_input_from<R: BufRead>(reader: R) -> Result<(Algorithm, Image)> {
+    let mut lines = reader.lines();
+    let alg = lines
+        .next()
+        .context("missing input")??
+        .parse::<Algorithm>()
+        .context("line 1")?;
+
+    if !lines.next().context("line 2: missing input")??.is_empty() {
+        bail!("line 2: expected a blank line after the algorithm");
     }
 
-    let mut image = Image::default();
-    for remaining_line in lines {
-        let remaining_line = remaining_line?;
-        let mut row = Vec::with_capacity(remaining_line.len());
-        for c in remaining_line.chars() {
-            row.push(c == '#');
+    let mut rows: Vec<Vec<bool>> = Vec::new();
+    for (n, line) in lines.enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            break;
+        }
+        let n = n + 3;
+        let row = line
+            .chars()
+            .enumerate()
+            .map(|(col, c)| {
+                parse_pixel(c).with_context(|| {
+                    format!(
+                        "line {n}, column {}: expected `#` or `.`, found `{c}`",
+                        col + 1
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if let Some(first) = rows.first() {
+            if row.len() != first.len() {
+                bail!(
+                    "line {n}: expected {} pixels, found {}",
+                    first.len(),
+                    row.len()
+                );
+            }
         }
-        image.rows.push(row);
+        rows.push(row);
+    }
+
+    Ok((alg, Image::from_rows(&rows)))
+}
+
+/// an image enhancement algorithm: what each pixel turns into, looked up by the 9 pixels around it, read in reading order as a binary number with lit pixels as 1s.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Algorithm([bool; 512]);
+
+impl Algorithm {
+    pub const fn new(table: [bool; 512]) -> Self {
+        Self(table)
+    }
+
+    /// the algorithm which turns a pixel with index `i` into `f(i)`.
+    pub fn from_fn(f: impl FnMut(usize) -> bool) -> Self {
+        Self(std::array::from_fn(f))
     }
 
-    Ok((alg, image))
+    pub fn table(&self) -> &[bool; 512] {
+        &self.0
+    }
+}
+
+impl From<[bool; 512]> for Algorithm {
+    fn from(table: [bool; 512]) -> Self {
+        Self(table)
+    }
+}
+
+impl Index<usize> for Algorithm {
+    type Output = bool;
+
+    fn index(&self, idx: usize) -> &bool {
+        &self.0[idx]
+    }
 }
 
-#[derive(Clone, Copy, Display, PartialEq, Eq)]
+impl FromStr for Algorithm {
+    type Err = anyhow::Error;
+
+    /// exactly 512 `#`s and `.`s.
+    fn from_str(s: &str) -> Result<Self> {
+        let mut table = [false; 512];
+        let mut len = 0;
+        for (i, c) in s.chars().enumerate() {
+            let px = parse_pixel(c).with_context(|| {
+                format!(
+                    "algorithm column {}: expected `#` or `.`, found `{c}`",
+                    i + 1
+                )
+            })?;
+            if let Some(entry) = table.get_mut(i) {
+                *entry = px;
+            }
+            len += 1;
+        }
+        if len != table.len() {
+            bail!(
+                "expected an algorithm of {} pixels, found {len}",
+                table.len()
+            );
+        }
+        Ok(Self(table))
+    }
+}
+
+impl Display for Algorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for &px in &self.0 {
+            write!(f, "{}", if px { '#' } else { '.' })?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, Hash)]
 #[display(fmt = "{},{}", x, y)]
 pub struct Point {
     pub x: i32,
@@ -41,79 +165,135 @@ impl Point {
         Self { x, y }
     }
 
-    pub const fn neighbors(&self) -> Neighbors {
-        Neighbors::new(*self)
+    /// the neighbors of a point in a 3x3 area around it, *including the point itself*, in reading order.
+    pub fn neighbors(&self) -> impl Iterator<Item = Point> {
+        Neighborhood::new(Shape::MooreWithCenter, Order::RowMajor)
+            .around((self.x, self.y))
+            .map(|(x, y)| Point::new(x, y))
     }
 }
 
-/// the neighbors of a point in a 3x3 area around it, *including the point itself*.
-pub struct Neighbors {
-    x: i32,
-    y: i32,
-    center: Point,
-}
+/// an image is stored sparsely if fewer than one in this many of the pixels in its bounding box differ from the background.
+pub const SPARSE_RATIO: usize = 16;
 
-impl Neighbors {
-    pub const fn new(center: Point) -> Self {
-        Self {
-            x: -1,
-            y: -1,
-            center,
-        }
-    }
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Pixels {
+    /// every pixel in the bounding box, row by row.
+    Dense(Vec<bool>),
+    /// just the pixels which differ from the background.
+    Sparse(HashSet<Point>),
 }
 
-impl Iterator for Neighbors {
-    type Item = Point;
+/// an infinite image: a background colour, and the pixels which differ from it. only the smallest rectangle holding all of those is stored, so the borders of the stored part are never all background.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    /// the top left corner of the bounding box.
+    origin: Point,
+    width: usize,
+    height: usize,
+    pixels: Pixels,
+    /// the colour of every pixel outside the bounding box.
+    background: bool,
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.y <= 1 {
-            let res = Some(Point {
-                x: self.center.x + self.x,
-                y: self.center.y + self.y,
-            });
+impl Default for Image {
+    fn default() -> Self {
+        Self::from_pixels(Vec::new(), false)
+    }
+}
 
-            if self.x == 1 {
-                self.x = -1;
-                self.y += 1;
-            } else {
-                self.x += 1;
+impl Image {
+    /// an image with `rows[0][0]` at the origin and a dark background.
+    pub fn from_rows(rows: &[Vec<bool>]) -> Self {
+        let mut lit = Vec::new();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, &px) in row.iter().enumerate() {
+                if px {
+                    lit.push(Point::new(
+                        i32::try_from(x).expect("image too wide"),
+                        i32::try_from(y).expect("image too tall"),
+                    ));
+                }
             }
+        }
+        Self::from_pixels(lit, false)
+    }
+
+    /// an image where every pixel is `background` apart from `exceptions`, which must all be different. the storage is picked by how spread out they are.
+    fn from_pixels(exceptions: Vec<Point>, background: bool) -> Self {
+        let Some(first) = exceptions.first() else {
+            return Self {
+                origin: Point::new(0, 0),
+                width: 0,
+                height: 0,
+                pixels: Pixels::Dense(Vec::new()),
+                background,
+            };
+        };
+        let (mut min, mut max) = (*first, *first);
+        for p in &exceptions {
+            min = Point::new(min.x.min(p.x), min.y.min(p.y));
+            max = Point::new(max.x.max(p.x), max.y.max(p.y));
+        }
+        let width = max.x.abs_diff(min.x) as usize + 1;
+        let height = max.y.abs_diff(min.y) as usize + 1;
 
-            res
+        let pixels = if exceptions.len().saturating_mul(SPARSE_RATIO) < width.saturating_mul(height)
+        {
+            Pixels::Sparse(exceptions.into_iter().collect())
         } else {
-            None
+            let mut dense = vec![background; width * height];
+            for p in exceptions {
+                dense[p.y.abs_diff(min.y) as usize * width + p.x.abs_diff(min.x) as usize] =
+                    !background;
+            }
+            Pixels::Dense(dense)
+        };
+        Self {
+            origin: min,
+            width,
+            height,
+            pixels,
+            background,
         }
     }
-}
 
-#[derive(Default)]
-pub struct Image {
-    pub rows: Vec<Vec<bool>>,
-    pub surrounded_by_true: bool,
-}
-
-impl Image {
-    /// (width, height)
+    /// (width, height) of the bounding box.
     pub fn dimensions(&self) -> (usize, usize) {
-        (
-            self.rows.first().map(Vec::len).unwrap_or_default(),
-            self.rows.len(),
-        )
+        (self.width, self.height)
+    }
+
+    /// the top left corner of the bounding box.
+    pub fn origin(&self) -> Point {
+        self.origin
+    }
+
+    /// the colour of every pixel outside the bounding box.
+    pub fn background(&self) -> bool {
+        self.background
+    }
+
+    pub fn is_sparse(&self) -> bool {
+        matches!(self.pixels, Pixels::Sparse(_))
     }
 
     pub fn get(&self, point: Point) -> bool {
-        if point.x < 0 || point.y < 0 {
-            self.surrounded_by_true
-        } else {
-            self.rows
-                .get(point.y as usize)
-                .and_then(|r| r.get(point.x as usize).copied())
-                .unwrap_or(self.surrounded_by_true)
+        match &self.pixels {
+            Pixels::Sparse(exceptions) => exceptions.contains(&point) != self.background,
+            Pixels::Dense(pixels) => {
+                let x = usize::try_from(i64::from(point.x) - i64::from(self.origin.x));
+                let y = usize::try_from(i64::from(point.y) - i64::from(self.origin.y));
+                match (x, y) {
+                    (Ok(x), Ok(y)) if x < self.width && y < self.height => {
+                        pixels[y * self.width + x]
+                    }
+                    _ => self.background,
+                }
+            }
         }
     }
 
-    pub fn get_enhanced_px(&self, point: Point, alg: &[bool]) -> bool {
+    pub fn get_enhanced_px(&self, point: Point, alg: &Algorithm) -> bool {
         let mut idx = 0;
         for neighbor in point.neighbors() {
             idx <<= 1;
@@ -124,45 +304,136 @@ impl Image {
         alg[idx]
     }
 
+    /// the points which differ from the background.
+    pub fn exceptions(&self) -> Vec<Point> {
+        match &self.pixels {
+            Pixels::Sparse(exceptions) => exceptions.iter().copied().collect(),
+            Pixels::Dense(_) => self
+                .area(0)
+                .filter(|&p| self.get(p) != self.background)
+                .collect(),
+        }
+    }
+
+    /// every point in the bounding box grown by `margin` on each side, row by row.
+    fn area(&self, margin: i32) -> impl Iterator<Item = Point> {
+        let Point { x: left, y: top } = self.origin;
+        let (width, height) = (self.width as i32, self.height as i32);
+        (top - margin..top + height + margin).flat_map(move |y| {
+            (left - margin..left + width + margin).map(move |x| Point::new(x, y))
+        })
+    }
+
     #[must_use]
-    pub fn enhance(&self, alg: &[bool]) -> Self {
-        let (width, height) = self.dimensions();
-        let mut new_img = Self {
-            rows: Vec::with_capacity(height+2),
-            surrounded_by_true: if self.surrounded_by_true {
-                *alg.last().unwrap()
-            } else {
-                *alg.first().unwrap()
-            },
+    pub fn enhance(&self, alg: &Algorithm) -> Self {
+        // far enough from the bounding box, every pixel's neighborhood is all background.
+        let background = alg[if self.background { 511 } else { 0 }];
+
+        let candidates = match &self.pixels {
+            Pixels::Sparse(exceptions) => exceptions
+                .iter()
+                .flat_map(Point::neighbors)
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .collect(),
+            Pixels::Dense(_) => self.area(1).collect::<Vec<_>>(),
         };
+        let exceptions = candidates
+            .into_iter()
+            .filter(|&p| self.get_enhanced_px(p, alg) != background)
+            .collect();
+        Self::from_pixels(exceptions, background)
+    }
+
+    /// the number of lit pixels, or `None` if the background is lit and there are infinitely many.
+    pub fn count(&self) -> Option<usize> {
+        if self.background {
+            return None;
+        }
+        Some(match &self.pixels {
+            Pixels::Sparse(exceptions) => exceptions.len(),
+            Pixels::Dense(pixels) => pixels.iter().filter(|p| **p).count(),
+        })
+    }
+
+    /// the pixels in the bounding box, one row at a time.
+    pub fn rows(&self) -> impl Iterator<Item = Vec<bool>> + '_ {
+        self.row_runs().map(|runs| {
+            runs.into_iter()
+                .flat_map(|(px, len)| iter::repeat_n(px, len))
+                .collect()
+        })
+    }
 
-        for y in -1..=i32::try_from(height).unwrap() {
-            let mut row = Vec::with_capacity(width+2);
-            for x in -1..=i32::try_from(width).unwrap() {
-                let value = self.get_enhanced_px(Point::new(x, y), alg);
-                row.push(value);
+    /// the bounding box one row at a time, as runs of `(colour, length)` which add up to the width. a sparse image only ever has its exceptions and one row's runs in memory, however big its bounding box is.
+    pub(crate) fn row_runs(&self) -> impl Iterator<Item = Vec<(bool, usize)>> + '_ {
+        fn push(runs: &mut Vec<(bool, usize)>, px: bool, len: usize) {
+            match runs.last_mut() {
+                _ if len == 0 => (),
+                Some((last, n)) if *last == px => *n += len,
+                _ => runs.push((px, len)),
             }
-            new_img.rows.push(row);
         }
-        new_img
+
+        let mut exceptions = match &self.pixels {
+            Pixels::Sparse(exceptions) => exceptions.iter().copied().collect(),
+            Pixels::Dense(_) => Vec::new(),
+        };
+        exceptions.sort_unstable_by_key(|p| (p.y, p.x));
+        let mut exceptions = exceptions.into_iter().peekable();
+
+        (0..self.height).map(move |y| {
+            let mut runs = Vec::new();
+            match &self.pixels {
+                Pixels::Dense(pixels) => {
+                    for &px in &pixels[y * self.width..][..self.width] {
+                        push(&mut runs, px, 1);
+                    }
+                }
+                Pixels::Sparse(_) => {
+                    let row = i64::from(self.origin.y) + y as i64;
+                    let mut x = 0;
+                    while let Some(p) = exceptions.next_if(|p| i64::from(p.y) == row) {
+                        let px = p.x.abs_diff(self.origin.x) as usize;
+                        push(&mut runs, self.background, px - x);
+                        push(&mut runs, !self.background, 1);
+                        x = px + 1;
+                    }
+                    push(&mut runs, self.background, self.width - x);
+                }
+            }
+            runs
+        })
     }
+}
 
-    pub fn count(&self) -> usize {
-        self.rows
-            .iter()
-            .map(|r| r.iter().filter(|p| **p).count())
-            .sum()
+impl ToNetpbm for Image {
+    /// draw the bounding box as a bitmap, with lit pixels in black, and where it is and what's around it in a comment.
+    fn to_netpbm(&self) -> Result<netpbm::Image> {
+        let (width, height) = self.dimensions();
+        let area = width
+            .checked_mul(height)
+            .filter(|&area| area <= export::MAX_BITMAP_PIXELS)
+            .with_context(|| format!("{width}x{height} is too big to draw"))?;
+        let mut pixels = Vec::with_capacity(area);
+        for runs in self.row_runs() {
+            for (px, len) in runs {
+                pixels.extend(iter::repeat_n(px, len));
+            }
+        }
+        let mut img = netpbm::Image::bitmap(width, height, pixels)?;
+        img.comments.push(format!(" {}", export::Header::of(self)));
+        Ok(img)
     }
 }
 
 impl Display for Image {
+    /// the bounding box.
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for row in &self.rows {
-            for point in row {
-                if *point {
-                    write!(f, "#")?;
-                } else {
-                    write!(f, ".")?;
+        for runs in self.row_runs() {
+            for (px, len) in runs {
+                for _ in 0..len {
+                    write!(f, "{}", if px { '#' } else { '.' })?;
                 }
             }
             writeln!(f)?;
@@ -171,3 +442,188 @@ impl Display for Image {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use common::netpbm::{Encoding, SequenceWriter};
+
+    use super::*;
+
+    fn example() -> (Algorithm, Image) {
+        parse_input_from(include_str!("../../input/20e.txt").as_bytes()).unwrap()
+    }
+
+    /// a rule which keeps every pixel as it is.
+    fn identity() -> Algorithm {
+        Algorithm::from_fn(|i| i & 0b000_010_000 != 0)
+    }
+
+    #[test]
+    fn netpbm_sequence() {
+        let (alg, mut img) = example();
+        let dir = std::env::temp_dir().join(format!("d20-netpbm-{}", std::process::id()));
+        let mut writer = SequenceWriter::new(&dir, "enhance", Encoding::Plain).unwrap();
+        for _ in 0..3 {
+            let path = writer.write(&img.to_netpbm().unwrap()).unwrap();
+            let bytes = std::fs::read(path).unwrap();
+            assert_eq!(
+                bytes,
+                img.to_netpbm().unwrap().to_bytes(Encoding::Plain).unwrap()
+            );
+
+            let read = netpbm::Image::from_bytes(&bytes).unwrap();
+            assert_eq!((read.width, read.height), img.dimensions());
+            assert_eq!(
+                read.data,
+                netpbm::Data::Bitmap(img.rows().flatten().collect())
+            );
+            img = img.enhance(&alg);
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn parse() {
+        let (alg, img) = example();
+        let line = include_str!("../../input/20e.txt").lines().next().unwrap();
+        assert_eq!(alg.to_string(), line);
+        assert_eq!(line.parse::<Algorithm>().unwrap(), alg);
+        assert_eq!(img.dimensions(), (5, 5));
+
+        let err = |s: &str| format!("{:#}", s.parse::<Algorithm>().unwrap_err());
+        assert_eq!(
+            err(&"#".repeat(511)),
+            "expected an algorithm of 512 pixels, found 511"
+        );
+        assert_eq!(
+            err(&".".repeat(513)),
+            "expected an algorithm of 512 pixels, found 513"
+        );
+        assert_eq!(
+            err(&format!("##.x{}", ".".repeat(508))),
+            "algorithm column 4: expected `#` or `.`, found `x`"
+        );
+
+        let err = |image: &str| {
+            let input = format!("{line}\n\n{image}");
+            format!("{:#}", parse_input_from(input.as_bytes()).unwrap_err())
+        };
+        assert_eq!(err("#..\n.#.\n#.\n"), "line 5: expected 3 pixels, found 2");
+        assert_eq!(
+            err("#..\n.#o\n"),
+            "line 4, column 3: expected `#` or `.`, found `o`"
+        );
+        assert_eq!(
+            format!("{:#}", parse_input_from(&b"#\n"[..]).unwrap_err()),
+            "line 1: expected an algorithm of 512 pixels, found 1"
+        );
+        assert_eq!(
+            format!(
+                "{:#}",
+                parse_input_from(format!("{line}\n#\n").as_bytes()).unwrap_err()
+            ),
+            "line 2: expected a blank line after the algorithm"
+        );
+        // a blank line ends the image.
+        let input = format!("{line}\n\n#.\n.#\n\n");
+        assert_eq!(
+            parse_input_from(input.as_bytes()).unwrap().1.count(),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn example_counts() {
+        let (alg, mut img) = example();
+        assert_eq!(img.count(), Some(10));
+        for _ in 0..2 {
+            img = img.enhance(&alg);
+        }
+        assert_eq!(img.count(), Some(35));
+        for _ in 2..50 {
+            img = img.enhance(&alg);
+        }
+        assert_eq!(img.count(), Some(3351));
+    }
+
+    #[test]
+    fn trimmed() {
+        let rows = ["......", "..#...", "...##.", "......"]
+            .map(|row| row.chars().map(|c| c == '#').collect::<Vec<_>>());
+        let img = Image::from_rows(&rows);
+        assert_eq!(img.dimensions(), (3, 2));
+        assert_eq!(img.origin(), Point::new(2, 1));
+        assert_eq!(img.to_string(), "#..\n.##\n");
+        assert!(img.get(Point::new(4, 2)));
+        assert!(!img.get(Point::new(-100, 2)));
+
+        // a pixel which dies leaves nothing behind.
+        let dot = Image::from_rows(&[vec![true]]);
+        let gone = dot.enhance(&Algorithm::life());
+        assert_eq!(gone.dimensions(), (0, 0));
+        assert_eq!(gone.count(), Some(0));
+        assert_eq!(gone, Image::default());
+    }
+
+    #[test]
+    fn bounded() {
+        // a blinker spins forever in the same place.
+        let blinker = Image::from_rows(&[vec![true, true, true]]);
+        let mut img = blinker.clone();
+        for i in 0..10_000 {
+            img = img.enhance(&Algorithm::life());
+            let expected = if i % 2 == 0 { (1, 3) } else { (3, 1) };
+            assert_eq!(img.dimensions(), expected);
+        }
+        assert_eq!(img, blinker);
+
+        // flipping every pixel flips the background too, but keeps the image the same size.
+        let flip = Algorithm::new(identity().table().map(|px| !px));
+        let (_, example) = example();
+        let mut img = example.clone();
+        for i in 0..10_000 {
+            img = img.enhance(&flip);
+            assert_eq!(img.background(), i % 2 == 0);
+            assert_eq!(img.dimensions(), example.dimensions());
+            assert_eq!(img.count().is_none(), img.background());
+        }
+        assert_eq!(img, example);
+    }
+
+    #[test]
+    fn sparse() {
+        // two pixels a long way apart.
+        let mut img =
+            Image::from_pixels(vec![Point::new(0, 0), Point::new(100_000, -100_000)], false);
+        assert!(img.is_sparse());
+        assert_eq!(img.dimensions(), (100_001, 100_001));
+        for _ in 0..100 {
+            img = img.enhance(&identity());
+        }
+        assert!(img.is_sparse());
+        assert!(img.get(Point::new(100_000, -100_000)));
+        assert!(!img.get(Point::new(1, 0)));
+        assert_eq!(img.count(), Some(2));
+        // a box that big can't be drawn, but it's never built just to find that out.
+        assert!(img.to_netpbm().is_err());
+
+        // a wide one can be printed a row at a time.
+        let wide = Image::from_pixels(vec![Point::new(0, 0), Point::new(99_999, 1)], false);
+        let text = wide.to_string();
+        let expected = format!("#{}\n{}#\n", ".".repeat(99_999), ".".repeat(99_999));
+        assert_eq!(text, expected);
+
+        // the same pixels come out of either storage.
+        let (alg, example) = example();
+        let mut dense = example.clone();
+        let mut sparse = example.clone();
+        sparse.pixels = Pixels::Sparse(example.exceptions().into_iter().collect());
+        for _ in 0..5 {
+            assert!(dense.rows().eq(sparse.rows()));
+            assert_eq!(dense.to_string(), sparse.to_string());
+            assert_eq!(dense.count(), sparse.count());
+            dense = dense.enhance(&alg);
+            sparse = sparse.enhance(&alg);
+        }
+    }
+}
@@ -0,0 +1,345 @@
+//! a bit-packed image, for enhancing big images quickly and on several threads at once.
+
+use std::{num::NonZeroUsize, thread};
+
+use crate::{Algorithm, Image, Point};
+
+/// the number of pixels below which `enhance` is usually faster than `enhance_parallel`.
+pub const MIN_PARALLEL_PIXELS: usize = 256 * 256;
+
+/// the bits of an index into the algorithm to keep after shifting it one to the left as the window slides right: the old middle and right columns, which become the new left and middle ones.
+const KEEP: usize = 0b110_110_110;
+
+/// the same as an `Image`, but with each row of the bounding box packed 64 pixels to a word, and always stored densely.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PackedImage {
+    /// the top left corner of the bounding box.
+    origin: Point,
+    width: usize,
+    height: usize,
+    /// the number of words in each row.
+    stride: usize,
+    /// pixel `x` of row `y` is bit `x % 64` of word `y * stride + x / 64`. bits past the end of a row are always 0.
+    words: Vec<u64>,
+    /// the colour of every pixel outside the bounding box.
+    background: bool,
+}
+
+impl Default for PackedImage {
+    fn default() -> Self {
+        Self::blank(Point::new(0, 0), 0, 0, false)
+    }
+}
+
+/// the bits of word `i` of a row which are inside a row `width` pixels long.
+fn row_mask(width: usize, i: usize) -> u64 {
+    match width.saturating_sub(64 * i) {
+        0 => 0,
+        n if n >= 64 => !0,
+        n => (1 << n) - 1,
+    }
+}
+
+impl PackedImage {
+    fn blank(origin: Point, width: usize, height: usize, background: bool) -> Self {
+        let stride = width.div_ceil(64);
+        let row = (0..stride)
+            .map(|i| if background { row_mask(width, i) } else { 0 })
+            .collect::<Vec<_>>();
+        Self {
+            origin,
+            width,
+            height,
+            stride,
+            words: row.repeat(height),
+            background,
+        }
+    }
+
+    /// (width, height) of the bounding box.
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    /// the top left corner of the bounding box.
+    pub fn origin(&self) -> Point {
+        self.origin
+    }
+
+    /// the colour of every pixel outside the bounding box.
+    pub fn background(&self) -> bool {
+        self.background
+    }
+
+    fn row(&self, y: usize) -> &[u64] {
+        &self.words[y * self.stride..(y + 1) * self.stride]
+    }
+
+    /// word `i` of row `y`, with the bits set where it differs from the background.
+    fn diff(&self, y: usize, i: usize) -> u64 {
+        let background = if self.background {
+            row_mask(self.width, i)
+        } else {
+            0
+        };
+        self.row(y)[i] ^ background
+    }
+
+    /// where `point` is in the bounding box, if it's in it at all.
+    fn offset(&self, point: Point) -> Option<(usize, usize)> {
+        let x = usize::try_from(i64::from(point.x) - i64::from(self.origin.x)).ok()?;
+        let y = usize::try_from(i64::from(point.y) - i64::from(self.origin.y)).ok()?;
+        (x < self.width && y < self.height).then_some((x, y))
+    }
+
+    pub fn get(&self, point: Point) -> bool {
+        match self.offset(point) {
+            Some((x, y)) => self.row(y)[x / 64] >> (x % 64) & 1 == 1,
+            None => self.background,
+        }
+    }
+
+    /// the number of lit pixels, or `None` if the background is lit and there are infinitely many.
+    pub fn count(&self) -> Option<usize> {
+        (!self.background).then(|| self.words.iter().map(|w| w.count_ones() as usize).sum())
+    }
+
+    /// `enhance_parallel` on one thread.
+    #[must_use]
+    pub fn enhance(&self, alg: &Algorithm) -> Self {
+        self.enhance_parallel(alg, NonZeroUsize::MIN)
+    }
+
+    /// `enhance` for small images, and `enhance_parallel` on every available thread for big ones.
+    #[must_use]
+    pub fn enhance_auto(&self, alg: &Algorithm) -> Self {
+        let threads = (self.width * self.height >= MIN_PARALLEL_PIXELS)
+            .then(|| thread::available_parallelism().ok())
+            .flatten();
+        self.enhance_parallel(alg, threads.unwrap_or(NonZeroUsize::MIN))
+    }
+
+    /// the same as `Image::enhance`, but with the work split between `threads` threads, each of which takes a band of rows.
+    #[must_use]
+    pub fn enhance_parallel(&self, alg: &Algorithm, threads: NonZeroUsize) -> Self {
+        // every pixel within one of the bounding box can change, and nothing else can apart from the background.
+        let background = alg[if self.background { 511 } else { 0 }];
+        let mut next = Self::blank(
+            Point::new(self.origin.x - 1, self.origin.y - 1),
+            self.width + 2,
+            self.height + 2,
+            false,
+        );
+        next.background = background;
+
+        let stride = next.stride;
+        let band_rows = next.height.div_ceil(threads.get());
+        if threads.get() == 1 {
+            self.enhance_band(alg, 0, &mut next.words, stride);
+        } else {
+            thread::scope(|s| {
+                let handles = next
+                    .words
+                    .chunks_mut(band_rows * stride)
+                    .enumerate()
+                    .map(|(i, band)| {
+                        s.spawn(move || self.enhance_band(alg, i * band_rows, band, stride))
+                    })
+                    .collect::<Vec<_>>();
+                for handle in handles {
+                    handle.join().expect("enhancing thread panicked");
+                }
+            });
+        }
+        next.trimmed()
+    }
+
+    /// work out the rows of the enhanced image from `first_row` onwards, into `out`, which is all 0s to start with. the enhanced image is one pixel bigger on each side, and its rows are `stride` words long.
+    ///
+    /// the index of each pixel is kept in a sliding window as it goes along the row: each step, the three columns move one to the left and the next column of pixels comes in on the right.
+    fn enhance_band(&self, alg: &Algorithm, first_row: usize, out: &mut [u64], stride: usize) {
+        let fill = if self.background { !0 } else { 0 };
+        let background = usize::from(self.background) * 0b001_001_001;
+
+        for (r, out_row) in out.chunks_mut(stride).enumerate() {
+            // row `y` of the output is centred on row `y - 1` of `self`.
+            let y = first_row + r;
+            let rows = [y.checked_sub(2), y.checked_sub(1), Some(y)].map(|row| {
+                row.filter(|&row| row < self.height)
+                    .map(|row| self.row(row))
+            });
+
+            let mut idx = 0;
+            let mut x = 0;
+            let mut push = |idx: usize| {
+                if alg[idx] {
+                    out_row[x / 64] |= 1 << (x % 64);
+                }
+                x += 1;
+            };
+
+            // the two columns to the left of the image are background.
+            for _ in 0..2 {
+                idx = (idx << 1) & KEEP | background;
+            }
+            for i in 0..self.stride {
+                let [above, middle, below] = rows.map(|row| row.map_or(fill, |row| row[i]));
+                for bit in 0..(self.width - 64 * i).min(64) {
+                    let column =
+                        (above >> bit & 1) << 6 | (middle >> bit & 1) << 3 | below >> bit & 1;
+                    idx = (idx << 1) & KEEP | column as usize;
+                    push(idx);
+                }
+            }
+            // and so are the two to the right.
+            for _ in 0..2 {
+                idx = (idx << 1) & KEEP | background;
+                push(idx);
+            }
+        }
+    }
+
+    /// cut off every row and column around the edges which is all background.
+    fn trimmed(self) -> Self {
+        let differs = |&y: &usize| (0..self.stride).any(|i| self.diff(y, i) != 0);
+        let Some(top) = (0..self.height).find(differs) else {
+            return Self::blank(Point::new(0, 0), 0, 0, self.background);
+        };
+        let bottom = (0..self.height).rfind(differs).unwrap();
+
+        let (mut left, mut right) = (usize::MAX, 0);
+        for y in top..=bottom {
+            for i in 0..self.stride {
+                let d = self.diff(y, i);
+                if d != 0 {
+                    left = left.min(64 * i + d.trailing_zeros() as usize);
+                    right = right.max(64 * i + 63 - d.leading_zeros() as usize);
+                }
+            }
+        }
+        if (top, bottom, left, right) == (0, self.height - 1, 0, self.width - 1) {
+            return self;
+        }
+
+        let width = right - left + 1;
+        let stride = width.div_ceil(64);
+        let mut words = Vec::with_capacity(stride * (bottom - top + 1));
+        for y in top..=bottom {
+            let row = self.row(y);
+            for j in 0..stride {
+                let (k, shift) = ((left + 64 * j) / 64, (left + 64 * j) % 64);
+                let mut word = row[k] >> shift;
+                if shift > 0 {
+                    if let Some(next) = row.get(k + 1) {
+                        word |= next << (64 - shift);
+                    }
+                }
+                words.push(word & row_mask(width, j));
+            }
+        }
+        Self {
+            origin: Point::new(self.origin.x + left as i32, self.origin.y + top as i32),
+            width,
+            height: bottom - top + 1,
+            stride,
+            words,
+            background: self.background,
+        }
+    }
+}
+
+impl From<&Image> for PackedImage {
+    fn from(image: &Image) -> Self {
+        let (width, height) = image.dimensions();
+        let mut packed = Self::blank(image.origin(), width, height, image.background());
+        for point in image.exceptions() {
+            let (x, y) = packed
+                .offset(point)
+                .expect("exceptions are in the bounding box");
+            packed.words[y * packed.stride + x / 64] ^= 1 << (x % 64);
+        }
+        packed
+    }
+}
+
+impl From<&PackedImage> for Image {
+    fn from(packed: &PackedImage) -> Self {
+        let mut exceptions = Vec::new();
+        for y in 0..packed.height {
+            for i in 0..packed.stride {
+                let mut d = packed.diff(y, i);
+                while d != 0 {
+                    let x = 64 * i + d.trailing_zeros() as usize;
+                    exceptions.push(Point::new(
+                        packed.origin.x + x as i32,
+                        packed.origin.y + y as i32,
+                    ));
+                    d &= d - 1;
+                }
+            }
+        }
+        Image::from_pixels(exceptions, packed.background)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::rng::Rng;
+
+    use super::*;
+    use crate::parse_input_from;
+
+    fn random_image(rng: &mut Rng, width: usize, height: usize, density: f64) -> Image {
+        let rows = (0..height)
+            .map(|_| (0..width).map(|_| rng.chance(density)).collect())
+            .collect::<Vec<_>>();
+        Image::from_rows(&rows)
+    }
+
+    #[test]
+    fn example() {
+        let (alg, img) = parse_input_from(include_str!("../../input/20e.txt").as_bytes()).unwrap();
+        let mut packed = PackedImage::from(&img);
+        assert_eq!(Image::from(&packed), img);
+        assert_eq!(packed.count(), Some(10));
+        for _ in 0..2 {
+            packed = packed.enhance(&alg);
+        }
+        assert_eq!(packed.count(), Some(35));
+        for _ in 2..50 {
+            packed = packed.enhance_parallel(&alg, NonZeroUsize::new(3).unwrap());
+        }
+        assert_eq!(packed.count(), Some(3351));
+
+        assert_eq!(PackedImage::from(&Image::default()), PackedImage::default());
+        assert_eq!(PackedImage::default().enhance(&alg), PackedImage::default());
+    }
+
+    #[test]
+    fn random_rules() {
+        let mut rng = Rng::new(47);
+        // sizes either side of a word, so that pixels cross between words as the image grows and shrinks.
+        for (width, height) in [(1, 1), (5, 3), (62, 4), (63, 2), (64, 7), (65, 1), (130, 9)] {
+            for _ in 0..20 {
+                let density = rng.unit();
+                let alg = Algorithm::from_fn(|_| rng.chance(density));
+                let lit = rng.unit();
+                let mut img = random_image(&mut rng, width, height, lit);
+                let mut packed = PackedImage::from(&img);
+                for step in 0..8 {
+                    img = img.enhance(&alg);
+                    let threads = NonZeroUsize::new(1 + step % 4).unwrap();
+                    packed = packed.enhance_parallel(&alg, threads);
+                    assert_eq!(Image::from(&packed), img);
+                    assert_eq!(packed.dimensions(), img.dimensions());
+                    assert_eq!(packed.origin(), img.origin());
+                    assert_eq!(packed.count(), img.count());
+                    assert_eq!(PackedImage::from(&img), packed);
+                }
+                for point in [img.origin(), Point::new(-3, 70), Point::new(100, 4)] {
+                    assert_eq!(packed.get(point), img.get(point));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,230 @@
+//! compiling cellular automaton rules, as written in golly, into enhancement algorithms: life-like rules such as `B3/S23`, and MAP strings.
+
+use anyhow::{bail, Context, Result};
+
+use crate::Algorithm;
+
+/// the bit of an index which is the pixel in the middle.
+const CENTER: usize = 0b000_010_000;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// the number of base64 characters in a MAP string, without padding: enough for 512 bits.
+const MAP_LEN: usize = 512_usize.div_ceil(6);
+
+/// the neighbor counts in one half of a life-like rule, as a bitset.
+fn counts(rule: &str, digits: &str) -> Result<u16> {
+    let mut set = 0;
+    for c in digits.chars() {
+        match c.to_digit(10) {
+            Some(n) if n <= 8 => set |= 1 << n,
+            _ => bail!("bad rule `{rule}`: `{c}` isn't a number of neighbors from 0 to 8"),
+        }
+    }
+    Ok(set)
+}
+
+impl Algorithm {
+    /// the outer totalistic rule where a dark pixel lights up if the number of lit pixels around it is in `birth`, and a lit pixel stays lit if it's in `survival`.
+    pub fn life_like(birth: &[u8], survival: &[u8]) -> Self {
+        Self::from_fn(|i| {
+            let neighbors = (i & !CENTER).count_ones() as u8;
+            if i & CENTER == 0 {
+                birth.contains(&neighbors)
+            } else {
+                survival.contains(&neighbors)
+            }
+        })
+    }
+
+    /// conway's game of life.
+    pub fn life() -> Self {
+        Self::life_like(&[3], &[2, 3])
+    }
+
+    /// parse a rule in any of the forms golly understands for these: `B3/S23` (in either order and either case), the older survival-first `23/3`, or a `MAP` string.
+    pub fn from_rule(rule: &str) -> Result<Self> {
+        if let Some(map) = rule.strip_prefix("MAP") {
+            return Self::from_map(map).with_context(|| format!("bad rule `{rule}`"));
+        }
+
+        let Some((first, second)) = rule.split_once('/') else {
+            bail!("bad rule `{rule}`: expected two halves separated by `/`");
+        };
+        fn tagged(half: &str) -> Option<(char, &str)> {
+            let mut chars = half.chars();
+            let tag = chars.next()?.to_ascii_uppercase();
+            matches!(tag, 'B' | 'S').then_some((tag, chars.as_str()))
+        }
+        let (birth, survival) = match (tagged(first), tagged(second)) {
+            (Some(('B', birth)), Some(('S', survival)))
+            | (Some(('S', survival)), Some(('B', birth))) => (birth, survival),
+            (None, None) => (second, first),
+            _ => bail!("bad rule `{rule}`: expected `B` and `S` halves"),
+        };
+        let (birth, survival) = (counts(rule, birth)?, counts(rule, survival)?);
+        let set = |counts: u16| (0..=8).filter(|n| counts >> n & 1 == 1).collect::<Vec<_>>();
+        Ok(Self::life_like(&set(birth), &set(survival)))
+    }
+
+    /// decode the part of a MAP string after `MAP`: the whole table, 512 bits in base64 with the first entry in the highest bit, optionally padded with `=`.
+    pub fn from_map(map: &str) -> Result<Self> {
+        let map = map.trim_end_matches('=');
+        let len = map.chars().count();
+        if len != MAP_LEN {
+            bail!("expected {MAP_LEN} base64 characters, found {len}");
+        }
+        let mut bits = Vec::with_capacity(MAP_LEN * 6);
+        for (i, c) in map.chars().enumerate() {
+            let Some(n) = BASE64.iter().position(|&b| char::from(b) == c) else {
+                bail!("character {}: `{}` isn't base64", i + 1, c.escape_default());
+            };
+            bits.extend((0..6).rev().map(|bit| n >> bit & 1 == 1));
+        }
+        Ok(Self::from_fn(|i| bits[i]))
+    }
+
+    /// the algorithm as a golly MAP string, without padding.
+    pub fn to_map(&self) -> String {
+        let mut map = String::from("MAP");
+        for chunk in self.table().chunks(6) {
+            let n = (0..6).fold(0, |n, bit| {
+                n << 1 | usize::from(chunk.get(bit) == Some(&true))
+            });
+            map.push(char::from(BASE64[n]));
+        }
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::rng::Rng;
+
+    use super::*;
+    use crate::{Image, Point};
+
+    fn image(rows: &[&str]) -> Image {
+        let rows = rows
+            .iter()
+            .map(|row| row.chars().map(|c| c == '#').collect())
+            .collect::<Vec<_>>();
+        Image::from_rows(&rows)
+    }
+
+    #[test]
+    fn rules() {
+        let life = Algorithm::life();
+        for rule in ["B3/S23", "b3/s23", "S23/B3", "23/3", "B3/S32"] {
+            assert_eq!(Algorithm::from_rule(rule).unwrap(), life, "{rule}");
+        }
+        assert_eq!(
+            Algorithm::from_rule(
+                "MAPARYXfhZofugWaH7oaIDogBZofuhogOiAaIDogIAAgAAWaH7oaIDogGiA6ICAAIAAaIDogIAAgACAAIAAAAAAAA"
+            )
+            .unwrap(),
+            life
+        );
+
+        // highlife is life, apart from a dark pixel with 6 lit neighbors.
+        let highlife = Algorithm::from_rule("B36/S23").unwrap();
+        for i in 0..512 {
+            let six = i & CENTER == 0 && (i & !CENTER).count_ones() == 6;
+            assert_eq!(highlife[i] != life[i], six);
+        }
+        // nothing ever changes.
+        let still = Algorithm::from_rule("B/S012345678").unwrap();
+        assert!((0..512).all(|i| still[i] == (i & CENTER != 0)));
+
+        for bad in ["B3S23", "B3/S29", "B3/B23", "B3/23", "B3/S2x", "MAPAB"] {
+            assert!(Algorithm::from_rule(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn maps() {
+        let life = Algorithm::life();
+        let map = life.to_map();
+        assert_eq!(map.len(), 3 + MAP_LEN);
+        assert_eq!(Algorithm::from_rule(&map).unwrap(), life);
+        assert_eq!(Algorithm::from_rule(&format!("{map}==")).unwrap(), life);
+
+        let mut rng = Rng::new(49);
+        for _ in 0..100 {
+            let alg = Algorithm::from_fn(|_| rng.chance(0.5));
+            assert_eq!(Algorithm::from_rule(&alg.to_map()).unwrap(), alg);
+        }
+
+        let err = Algorithm::from_map(&format!("{}!", &map[3..MAP_LEN + 2])).unwrap_err();
+        assert_eq!(err.to_string(), "character 86: `!` isn't base64");
+
+        // characters are counted as characters, not bytes.
+        let err = Algorithm::from_map(&format!("{}é", &map[3..MAP_LEN + 1])).unwrap_err();
+        assert_eq!(err.to_string(), "expected 86 base64 characters, found 85");
+        let err = Algorithm::from_map(&format!("{}é", &map[3..MAP_LEN + 2])).unwrap_err();
+        assert_eq!(err.to_string(), "character 86: `\\u{e9}` isn't base64");
+    }
+
+    #[test]
+    fn oscillators() {
+        let life = Algorithm::life();
+
+        let block = image(&["##", "##"]);
+        assert_eq!(block.enhance(&life), block);
+
+        let blinker = image(&["###"]);
+        let turned = blinker.enhance(&life);
+        assert_eq!(turned.to_string(), "#\n#\n#\n");
+        assert_eq!(turned.origin(), Point::new(1, -1));
+        assert_eq!(turned.enhance(&life), blinker);
+
+        // a pulsar has period 3.
+        let pulsar = image(&[
+            "..###...###..",
+            ".............",
+            "#....#.#....#",
+            "#....#.#....#",
+            "#....#.#....#",
+            "..###...###..",
+            ".............",
+            "..###...###..",
+            "#....#.#....#",
+            "#....#.#....#",
+            "#....#.#....#",
+            ".............",
+            "..###...###..",
+        ]);
+        let mut img = pulsar.clone();
+        for step in 1..=30 {
+            img = img.enhance(&life);
+            assert_eq!(img == pulsar, step % 3 == 0);
+        }
+    }
+
+    #[test]
+    fn spaceships() {
+        let life = Algorithm::life();
+
+        // a glider moves one pixel diagonally every 4 generations.
+        let glider = image(&[".#.", "..#", "###"]);
+        let mut img = glider.clone();
+        for step in 1..=400 {
+            img = img.enhance(&life);
+            assert_eq!(img.count(), Some(5));
+            if step % 4 == 0 {
+                let moved = step / 4;
+                assert_eq!(img.origin(), Point::new(moved, moved));
+                assert!(img.rows().eq(glider.rows()));
+            }
+        }
+
+        // and a lightweight spaceship moves two pixels left every 4.
+        let lwss = image(&[".#..#", "#....", "#...#", "####."]);
+        let mut img = lwss.clone();
+        for _ in 0..4 {
+            img = img.enhance(&life);
+        }
+        assert_eq!(img.origin(), Point::new(-2, 0));
+        assert!(img.rows().eq(lwss.rows()));
+    }
+}
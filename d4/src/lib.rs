@@ -22,9 +22,8 @@ impl Board {
     pub fn has_won(&self) -> bool {
         let mut row_pr = [true; COL_SIZE];
         let mut col_pr = [true; ROW_SIZE];
-        for y in 0..COL_SIZE {
-            for x in 0..ROW_SIZE {
-                let b = self.mask[y][x];
+        for (y, row) in self.mask.iter().enumerate() {
+            for (x, b) in row.iter().enumerate() {
                 row_pr[y] &= b;
                 col_pr[x] &= b;
             }
@@ -4,25 +4,19 @@ use std::io::stdin;
 
 use anyhow::Result;
 
-use crate::d15::*;
-
-pub fn neighbors(xmax: usize, ymax: usize, (x, y): (usize, usize)) -> Vec<(usize, usize)> {
-    let mut res = Vec::new();
+use common::neighbors::{Bounds, Neighborhood, Order, Shape};
 
-    if x < xmax {
-        res.push((y, x + 1))
-    }
-    if y > 0 {
-        res.push((y - 1, x))
-    }
-    if x > 0 {
-        res.push((y, x - 1))
-    }
-    if y < ymax {
-        res.push((y + 1, x))
-    }
+use crate::d15::*;
 
-    res
+/// the orthogonal neighbors of `center` with coordinates no greater than `xmax` and `ymax`, as `(x, y)`.
+pub fn neighbors(
+    xmax: usize,
+    ymax: usize,
+    center: (usize, usize),
+) -> impl Iterator<Item = (usize, usize)> {
+    Neighborhood::new(Shape::VonNeumann, Order::CounterClockwise)
+        .bounded(Bounds::new((0, 0), (xmax + 1, ymax + 1)))
+        .around(center)
 }
 
 pub fn a_star(points: &mut Vec<Vec<Point>>, goal: (usize, usize)) -> u16 {